@@ -0,0 +1,73 @@
+use anyhow::{Context, Result, bail};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::extract::FileKind;
+use crate::package::{PackageArchive, is_valid_guid, read_pathname, split_entry_path};
+use crate::stream::{MAX_BUFFERED_BYTES, PendingAsset};
+
+/// Streams the `asset` (or `asset.meta`) of the entry with GUID or pathname `asset` into `out`
+///
+/// Reading stops as soon as the file has been copied. Looking up by pathname, files that come
+/// before their `pathname` in the archive are buffered (spilling to a temporary file) until
+/// it shows up. Returns whether the file was found.
+pub(crate) fn copy_file(mut archive: PackageArchive, asset: &str, kind: FileKind, out: &mut impl Write) -> Result<bool> {
+    let wanted = match kind {
+        FileKind::Asset => "asset",
+        FileKind::Meta => "asset.meta",
+        FileKind::Folder => bail!("Error: Folders have no contents to print."),
+    };
+    let by_guid = is_valid_guid(asset);
+
+    // GUID whose pathname matched, while its file has not been seen yet
+    let mut matched: Option<String> = None;
+    // GUIDs whose pathname is known not to match
+    let mut other: HashSet<String> = HashSet::new();
+    let mut pending: HashMap<String, PendingAsset> = HashMap::new();
+    let mut buffered_bytes: u64 = 0;
+
+    for entry in archive.entries().context("Error reading package contents")? {
+        let mut entry = entry.context("Error reading package contents")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let Some((guid, name)) = split_entry_path(&entry.path()?) else {
+            continue;
+        };
+
+        if name == wanted {
+            if by_guid && guid != asset {
+                continue;
+            }
+            if by_guid || matched.as_ref() == Some(&guid) {
+                io::copy(&mut entry, out)?;
+                return Ok(true);
+            }
+            if matched.is_none() && !other.contains(&guid) {
+                // Its pathname may still turn out to be the one asked for
+                let size = entry.header().size()?;
+                let data = PendingAsset::read(&mut entry, size, MAX_BUFFERED_BYTES.saturating_sub(buffered_bytes))?;
+                buffered_bytes += data.len();
+                pending.insert(guid, data);
+            }
+        } else if name == "pathname" && !by_guid && matched.is_none() {
+            let pathname = read_pathname(&mut entry)?;
+            let data = pending.remove(&guid);
+            if let Some(data) = &data {
+                buffered_bytes -= data.len();
+            }
+
+            if pathname == asset {
+                if let Some(data) = data {
+                    io::copy(&mut data.into_reader()?, out)?;
+                    return Ok(true);
+                }
+                matched = Some(guid);
+            } else {
+                other.insert(guid);
+            }
+        }
+    }
+    Ok(false)
+}
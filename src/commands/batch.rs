@@ -0,0 +1,224 @@
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use unitypackage_extractor::{ExtractAction, ExtractOptions, ExtractReport, Manifest, PathFilter, UnityPackage};
+
+use super::extract::{parse_jobs, print_record, summarize};
+use super::option_value;
+use super::output::{OutputFormat, print_json_records};
+
+/// Outcome of one package of the batch
+struct BatchResult {
+    package: PathBuf,
+    destination: PathBuf,
+    outcome: Result<ExtractReport>,
+}
+
+/// `batch <file.unitypackage|folder>... [--output <dir>] [--shared] [--no-meta] [--include <glob>]...
+/// [--exclude <glob>]... [--on-conflict <policy>] [--dry-run] [--atomic] [--jobs <n>] [--no-receipt]
+/// [--format <text|json|ndjson>]`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut inputs = Vec::new();
+    let mut format = OutputFormat::Text;
+    let mut options = ExtractOptions {
+        jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+        ..ExtractOptions::default()
+    };
+    let mut includes = Vec::new();
+    let mut excludes = Vec::new();
+    let mut output_path = None;
+    let mut shared = false;
+    let mut write_receipt = true;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "-o" => output_path = Some(option_value(arg, args.next())?),
+            "--shared" => shared = true,
+            "--no-meta" => options.extract_meta = false,
+            "--include" => includes.push(option_value(arg, args.next())?),
+            "--exclude" => excludes.push(option_value(arg, args.next())?),
+            "--dry-run" => options.dry_run = true,
+            "--atomic" => options.atomic = true,
+            "--jobs" | "-j" => options.jobs = parse_jobs(arg, args.next())?,
+            "--on-conflict" => options.conflict = option_value(arg, args.next())?.parse()?,
+            "--no-receipt" => write_receipt = false,
+            "--format" => format = option_value(arg, args.next())?.parse()?,
+            _ if arg.starts_with('-') => bail!("Error: Unknown option '{}' for batch.", arg),
+            _ => inputs.push(Path::new(arg)),
+        }
+    }
+
+    options.filter = PathFilter::new(includes, excludes)?;
+
+    if inputs.is_empty() {
+        bail!("Error: You must specify at least one .unitypackage file or folder.");
+    }
+    let packages = find_packages(&inputs)?;
+    if packages.is_empty() {
+        bail!("Error: No .unitypackage files found.");
+    }
+
+    let cwd = env::current_dir()?;
+    let output_path = output_path.map(Path::new).unwrap_or(&cwd);
+    let destinations = destinations(&packages, output_path, shared);
+
+    if format.is_text() && options.dry_run {
+        println!("Dry run: nothing will be written to '{}'.", output_path.display());
+    }
+
+    let total = packages.len();
+    let mut results = Vec::new();
+    for (i, (package, destination)) in packages.into_iter().zip(destinations).enumerate() {
+        if format.is_text() {
+            println!("[{}/{}] '{}' -> '{}'", i + 1, total, package.display(), destination.display());
+        }
+        let outcome = extract_one(&package, &destination, &options, write_receipt, format);
+        if format.is_text() {
+            match &outcome {
+                Ok(report) => println!("    OK: {}", summarize(report).unwrap_or_else(|| "nothing to extract".to_string())),
+                Err(e) => println!("    FAILED: {:#}", e),
+            }
+        }
+        results.push(BatchResult { package, destination, outcome });
+    }
+
+    let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+    if format.is_text() {
+        print_summary(&results);
+    } else {
+        print_json_records(format, results.iter().map(result_json).collect())?;
+    }
+
+    if failed > 0 {
+        bail!("Error: {} of {} package(s) could not be extracted.", failed, results.len());
+    }
+    Ok(())
+}
+
+/// Every package given directly or found (recursively) in the given folders, in a stable order
+fn find_packages(inputs: &[&Path]) -> Result<Vec<PathBuf>> {
+    let mut packages = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut found = Vec::new();
+            walk(input, &mut found)?;
+            found.sort();
+            packages.extend(found);
+        } else if input.is_file() {
+            packages.push(input.to_path_buf());
+        } else {
+            bail!("Error: The file '{}' does not exist.", input.display());
+        }
+    }
+    Ok(packages)
+}
+
+fn walk(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Could not read folder '{}'", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, found)?;
+        } else if is_package(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_package(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("unitypackage"))
+}
+
+/// Output folder of each package: `<output>/<package name>`, numbered when two packages share
+/// a name, or `<output>` itself for a shared tree
+fn destinations(packages: &[PathBuf], output_path: &Path, shared: bool) -> Vec<PathBuf> {
+    if shared {
+        return vec![output_path.to_path_buf(); packages.len()];
+    }
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    packages
+        .iter()
+        .map(|package| {
+            let stem = package.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let count = seen.entry(stem.to_lowercase()).or_default();
+            *count += 1;
+            if *count == 1 {
+                output_path.join(&stem)
+            } else {
+                output_path.join(format!("{} ({})", stem, count))
+            }
+        })
+        .collect()
+}
+
+/// Extracts a single package, printing only warnings, and writes its receipt
+fn extract_one(
+    package_path: &Path,
+    destination: &Path,
+    options: &ExtractOptions,
+    write_receipt: bool,
+    format: OutputFormat,
+) -> Result<ExtractReport> {
+    let package = UnityPackage::open(package_path)?;
+    let report = package.extract_with(destination, options, |record| {
+        if format.is_text() && record.action == ExtractAction::SkippedOutsideDestination {
+            print_record(record, destination, options.dry_run);
+        }
+    })?;
+
+    if write_receipt && !options.dry_run {
+        let mut manifest = Manifest::from_report(&report);
+        manifest.package = Some(Manifest::package_name(package.path()));
+        manifest.save_receipt(&report, &Manifest::receipt_path(destination, package.path()))?;
+    }
+    Ok(report)
+}
+
+/// Prints the packages that failed, then how many succeeded
+fn print_summary(results: &[BatchResult]) {
+    let failed: Vec<&BatchResult> = results.iter().filter(|r| r.outcome.is_err()).collect();
+
+    println!();
+    if !failed.is_empty() {
+        println!("Failed:");
+        for result in &failed {
+            if let Err(e) = &result.outcome {
+                println!("  '{}': {:#}", result.package.display(), e);
+            }
+        }
+    }
+    println!(
+        "Summary: {} package(s), {} extracted, {} failed",
+        results.len(),
+        results.len() - failed.len(),
+        failed.len()
+    );
+}
+
+fn result_json(result: &BatchResult) -> Value {
+    let mut counts = Map::new();
+    let error = match &result.outcome {
+        Ok(report) => {
+            for record in &report.records {
+                let count = counts.entry(record.action.as_str()).or_insert(json!(0));
+                *count = json!(count.as_u64().unwrap_or(0) + 1);
+            }
+            Value::Null
+        }
+        Err(e) => json!(format!("{:#}", e)),
+    };
+
+    json!({
+        "package": result.package.display().to_string(),
+        "destination": result.destination.display().to_string(),
+        "status": if result.outcome.is_ok() { "ok" } else { "failed" },
+        "error": error,
+        "actions": counts,
+    })
+}
@@ -0,0 +1,35 @@
+use anyhow::{Result, bail};
+use std::io::{self, BufWriter, Write};
+use unitypackage_extractor::FileKind;
+
+use super::open_package;
+
+/// `cat <file.unitypackage|-> <pathname|guid> [--meta]`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut positional = Vec::new();
+    let mut kind = FileKind::Asset;
+
+    for arg in args {
+        match arg.as_str() {
+            "--meta" => kind = FileKind::Meta,
+            _ if arg.starts_with('-') && arg != "-" => bail!("Error: Unknown option '{}' for cat.", arg),
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    let [package_path, asset] = positional.as_slice() else {
+        bail!("Error: Usage: cat <file.unitypackage> <pathname|guid>");
+    };
+
+    let package = open_package(package_path)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    if !package.copy_file(asset, kind, &mut out)? {
+        let file = match kind {
+            FileKind::Meta => "meta",
+            _ => "asset",
+        };
+        bail!("Error: No {} for '{}' in the package.", file, asset);
+    }
+    out.flush()?;
+    Ok(())
+}
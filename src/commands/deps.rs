@@ -0,0 +1,130 @@
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+use unitypackage_extractor::deps::is_builtin_guid;
+use unitypackage_extractor::DependencyGraph;
+
+use super::{open_package, option_value};
+
+/// `deps <file.unitypackage|-> [--missing] [--format <text|json|dot>]`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut package_path = None;
+    let mut format = "text".to_string();
+    let mut missing = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--missing" => missing = true,
+            "--format" => format = option_value(arg, args.next())?.to_string(),
+            _ if arg.starts_with('-') && arg != "-" => bail!("Error: Unknown option '{}' for deps.", arg),
+            _ if package_path.is_none() => package_path = Some(arg),
+            _ => bail!("Error: Unexpected argument '{}' for deps.", arg),
+        }
+    }
+
+    let Some(package_path) = package_path else {
+        bail!("Error: You must specify the .unitypackage file to scan.");
+    };
+
+    let graph = open_package(package_path)?.dependency_graph()?;
+
+    if missing {
+        return report_unresolved(&graph, &format);
+    }
+
+    match format.as_str() {
+        "text" => print_text(&graph),
+        "json" => println!("{}", serde_json::to_string_pretty(&graph_json(&graph))?),
+        "dot" => print!("{}", graph.to_dot()),
+        _ => bail!("Error: Unknown format '{}' for deps. Expected text, json or dot.", format),
+    }
+    Ok(())
+}
+
+fn print_text(graph: &DependencyGraph) {
+    let mut assets: Vec<&String> = graph.references.keys().collect();
+    assets.sort_by_key(|guid| graph.pathname(guid));
+
+    for guid in assets {
+        println!("{}", graph.pathname(guid).unwrap_or(guid));
+        for dep in graph.dependencies(guid) {
+            match graph.pathname(dep) {
+                Some(pathname) => println!("  -> {}", pathname),
+                None if is_builtin_guid(dep) => println!("  -> {} (built-in resource)", dep),
+                None => println!("  -> {} (not in package)", dep),
+            }
+        }
+    }
+}
+
+/// `{"nodes": [{guid, pathname}], "edges": [{from, to, from_pathname, to_pathname}]}`
+fn graph_json(graph: &DependencyGraph) -> Value {
+    let nodes: Vec<Value> = graph
+        .pathnames
+        .iter()
+        .map(|(guid, pathname)| json!({ "guid": guid, "pathname": pathname }))
+        .collect();
+
+    let edges: Vec<Value> = graph
+        .references
+        .iter()
+        .flat_map(|(from, deps)| {
+            deps.iter().map(move |to| {
+                json!({
+                    "from": from,
+                    "to": to,
+                    "from_pathname": graph.pathname(from),
+                    "to_pathname": graph.pathname(to),
+                })
+            })
+        })
+        .collect();
+
+    json!({ "nodes": nodes, "edges": edges })
+}
+
+/// Prints references to GUIDs missing from the package and fails if there are any
+fn report_unresolved(graph: &DependencyGraph, format: &str) -> Result<()> {
+    let unresolved = graph.unresolved();
+
+    match format {
+        "text" => {
+            let mut assets: Vec<&str> = unresolved.keys().copied().collect();
+            assets.sort_by_key(|guid| graph.pathname(guid));
+
+            for guid in assets {
+                println!("{}", graph.pathname(guid).unwrap_or(guid));
+                for dep in &unresolved[guid] {
+                    println!("  -> {} (not in package)", dep);
+                }
+            }
+        }
+        "json" => {
+            let records: Vec<Value> = unresolved
+                .iter()
+                .map(|(guid, missing)| {
+                    json!({
+                        "guid": guid,
+                        "pathname": graph.pathname(guid),
+                        "missing": missing,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&records)?);
+        }
+        _ => bail!("Error: Unknown format '{}' for deps --missing. Expected text or json.", format),
+    }
+
+    if !unresolved.is_empty() {
+        let total: usize = unresolved.values().map(|missing| missing.len()).sum();
+        bail!(
+            "Error: {} asset(s) reference {} GUID(s) that are not in the package.",
+            unresolved.len(),
+            total
+        );
+    }
+    if format == "text" {
+        println!("All GUID references are resolved.");
+    }
+    Ok(())
+}
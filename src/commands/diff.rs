@@ -0,0 +1,163 @@
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use unitypackage_extractor::diff::{EntryChange, EntryDigest, MAX_TEXT_DIFF_BYTES, unified_text_diff};
+use unitypackage_extractor::{PackageDiff, UnityPackage};
+
+use super::option_value;
+use super::output::{OutputFormat, print_json_records};
+
+/// `diff <old.unitypackage> <new.unitypackage> [--unified] [--format <text|json|ndjson>]`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut positional = Vec::new();
+    let mut unified = false;
+    let mut format = OutputFormat::Text;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--unified" | "-u" => unified = true,
+            "--format" => format = option_value(arg, args.next())?.parse()?,
+            _ if arg.starts_with('-') => bail!("Error: Unknown option '{}' for diff.", arg),
+            _ => positional.push(arg),
+        }
+    }
+
+    let [old_path, new_path] = positional.as_slice() else {
+        bail!("Error: Usage: diff <old.unitypackage> <new.unitypackage>");
+    };
+
+    let old = UnityPackage::open(old_path)?;
+    let new = UnityPackage::open(new_path)?;
+    let diff = PackageDiff::between(&old, &new)?;
+
+    if !format.is_text() {
+        let records = diff.changes.iter().map(change_json).collect();
+        return print_json_records(format, records);
+    }
+
+    for change in &diff.changes {
+        println!("{}  {}", status(change), describe(change));
+    }
+
+    if unified {
+        print_unified(&diff, &old, &new)?;
+    }
+
+    print_summary(&diff);
+    Ok(())
+}
+
+/// Status letters: `A` added, `D` removed, `R` moved, `M` asset modified, `m` meta modified
+fn status(change: &EntryChange) -> String {
+    if change.is_added() {
+        return "A  ".to_string();
+    }
+    if change.is_removed() {
+        return "D  ".to_string();
+    }
+    [
+        (change.is_moved(), 'R'),
+        (change.asset_modified(), 'M'),
+        (change.meta_modified(), 'm'),
+    ]
+    .iter()
+    .map(|&(set, flag)| if set { flag } else { ' ' })
+    .collect()
+}
+
+fn describe(change: &EntryChange) -> String {
+    match (&change.old, &change.new) {
+        (Some(old), Some(new)) if old.pathname != new.pathname => {
+            format!("{} -> {}  ({})", old.pathname, new.pathname, change.guid)
+        }
+        _ => format!("{}  ({})", change.pathname(), change.guid),
+    }
+}
+
+/// Prints unified diffs of the modified text assets
+fn print_unified(diff: &PackageDiff, old: &UnityPackage, new: &UnityPackage) -> Result<()> {
+    let modified: HashSet<String> = diff
+        .changes
+        .iter()
+        .filter(|c| c.asset_modified())
+        .map(|c| c.guid.clone())
+        .collect();
+    if modified.is_empty() {
+        return Ok(());
+    }
+
+    // Binary and very large assets are not read, so they are missing here
+    let old_assets = old.text_asset_contents(&modified)?;
+    let new_assets = new.text_asset_contents(&modified)?;
+
+    for change in diff.changes.iter().filter(|c| c.asset_modified()) {
+        let (Some(old_entry), Some(new_entry)) = (&change.old, &change.new) else {
+            continue;
+        };
+        let old_data = text_contents(&old_assets, &change.guid, old_entry);
+        let new_data = text_contents(&new_assets, &change.guid, new_entry);
+        let text = match (old_data, new_data) {
+            (Some(old_data), Some(new_data)) => unified_text_diff(old_data, new_data, &old_entry.pathname, &new_entry.pathname),
+            _ => None,
+        };
+
+        println!();
+        match text {
+            Some(text) => print!("{}", text),
+            None if old_entry.size.max(new_entry.size) > MAX_TEXT_DIFF_BYTES => {
+                println!("Asset '{}' differs (too large to diff)", new_entry.pathname)
+            }
+            None => println!("Binary asset '{}' differs", new_entry.pathname),
+        }
+    }
+    Ok(())
+}
+
+/// Contents to diff: empty for an entry without an asset, `None` for one that was not read (binary)
+fn text_contents<'a>(assets: &'a HashMap<String, Vec<u8>>, guid: &str, entry: &EntryDigest) -> Option<&'a [u8]> {
+    match entry.asset_hash {
+        Some(_) => assets.get(guid).map(Vec::as_slice),
+        None => Some(&[]),
+    }
+}
+
+fn print_summary(diff: &PackageDiff) {
+    let count = |predicate: fn(&EntryChange) -> bool| diff.changes.iter().filter(|c| predicate(c)).count();
+
+    println!();
+    if diff.is_empty() {
+        println!("No differences.");
+    } else {
+        println!(
+            "{} added, {} removed, {} moved, {} assets modified, {} metas modified",
+            count(EntryChange::is_added),
+            count(EntryChange::is_removed),
+            count(EntryChange::is_moved),
+            count(EntryChange::asset_modified),
+            count(EntryChange::meta_modified),
+        );
+    }
+}
+
+fn change_json(change: &EntryChange) -> Value {
+    let status = if change.is_added() {
+        "added"
+    } else if change.is_removed() {
+        "removed"
+    } else {
+        "changed"
+    };
+
+    json!({
+        "guid": change.guid,
+        "status": status,
+        "old_pathname": change.old.as_ref().map(|d| &d.pathname),
+        "new_pathname": change.new.as_ref().map(|d| &d.pathname),
+        "moved": change.is_moved(),
+        "asset_modified": change.asset_modified(),
+        "meta_modified": change.meta_modified(),
+        "old_size": change.old.as_ref().map(|d| d.size),
+        "new_size": change.new.as_ref().map(|d| d.size),
+    })
+}
@@ -0,0 +1,333 @@
+use anyhow::{Context, Result, bail};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::env;
+use std::io::{self, BufWriter};
+use std::thread;
+use std::path::Path;
+use std::time::Instant;
+use tempfile::NamedTempFile;
+use unitypackage_extractor::{ExtractAction, ExtractOptions, ExtractRecord, ExtractReport, FileKind, Manifest, PathFilter, UnityPackage};
+
+use super::{open_package, option_value};
+use super::output::{OutputFormat, extract_report_json, print_json_records};
+use super::progress::ProgressPrinter;
+
+/// `<file.unitypackage|-> [output_path] [--no-meta] [--include <glob>]... [--exclude <glob>]...
+/// [--with-deps <pathname|guid>]... [--on-conflict <policy>] [--dry-run] [--format <text|json|ndjson>]
+/// [--manifest <file>] [--no-receipt] [--atomic] [--jobs <n>] [--progress] [--to-tar] [--to-zip <file> [--previews]]`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut positional = Vec::new();
+    let mut format = OutputFormat::Text;
+    let mut options = ExtractOptions {
+        jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+        ..ExtractOptions::default()
+    };
+    let mut includes = Vec::new();
+    let mut excludes = Vec::new();
+    let mut with_deps = Vec::new();
+    let mut manifest_path = None;
+    let mut write_receipt = true;
+    let mut show_progress = false;
+    let mut to_tar = false;
+    let mut to_zip = None;
+    let mut previews = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-meta" => options.extract_meta = false,
+            "--include" => includes.push(option_value(arg, args.next())?),
+            "--exclude" => excludes.push(option_value(arg, args.next())?),
+            "--with-deps" => with_deps.push(option_value(arg, args.next())?),
+            "--dry-run" => options.dry_run = true,
+            "--atomic" => options.atomic = true,
+            "--jobs" | "-j" => options.jobs = parse_jobs(arg, args.next())?,
+            "--format" => format = option_value(arg, args.next())?.parse()?,
+            "--on-conflict" => options.conflict = option_value(arg, args.next())?.parse()?,
+            "--manifest" => manifest_path = Some(option_value(arg, args.next())?),
+            "--no-receipt" => write_receipt = false,
+            "--progress" => show_progress = true,
+            "--to-tar" => to_tar = true,
+            "--to-zip" => to_zip = Some(Path::new(option_value(arg, args.next())?)),
+            "--previews" => previews = true,
+            _ if arg.starts_with("--") => bail!("Error: Unknown option '{}'.", arg),
+            _ => positional.push(arg),
+        }
+    }
+
+    options.filter = PathFilter::new(includes, excludes)?;
+    if show_progress && !format.is_text() {
+        bail!("Error: Option '--progress' can only be used with the text format.");
+    }
+
+    let Some(package_path) = positional.first() else {
+        bail!("Error: You must specify at least the .unitypackage file.");
+    };
+    if positional.len() > 2 {
+        bail!("Error: Unexpected argument '{}'.", positional[2]);
+    }
+
+    // Check input file existence
+    let package = open_package(package_path)?;
+
+    if !with_deps.is_empty() && package.is_stdin() {
+        bail!("Error: Option '--with-deps' reads the package twice and cannot be used with standard input.");
+    }
+    if to_tar {
+        if positional.len() > 1 {
+            bail!("Error: Option '--to-tar' writes to standard output and takes no output path.");
+        }
+        if !format.is_text() || show_progress || manifest_path.is_some() || to_zip.is_some() {
+            bail!("Error: Option '--to-tar' cannot be combined with '--format', '--progress', '--manifest' or '--to-zip'.");
+        }
+    }
+    if to_zip.is_some() {
+        if positional.len() > 1 {
+            bail!("Error: Option '--to-zip' takes the zip file instead of an output path.");
+        }
+        if show_progress || manifest_path.is_some() {
+            bail!("Error: Option '--to-zip' cannot be combined with '--progress' or '--manifest'.");
+        }
+    } else if previews {
+        bail!("Error: Option '--previews' can only be used with '--to-zip'.");
+    }
+
+    if !with_deps.is_empty() {
+        // With --to-tar, standard output only carries the tar stream
+        options.guids = Some(dependency_closure(&package, &with_deps, format.is_text() && !to_tar)?);
+    }
+
+    if to_tar {
+        return write_tar(&package, &options);
+    }
+    if let Some(zip_path) = to_zip {
+        return write_zip(&package, zip_path, &options, previews, format);
+    }
+
+    let output_path = positional.get(1).map(Path::new);
+
+    let start_time = Instant::now();
+    let report = extract_package(&package, output_path, &options, format, show_progress)?;
+    let duration = start_time.elapsed();
+
+    if !options.dry_run {
+        let mut manifest = Manifest::from_report(&report);
+        // A package read from standard input has no name to file its receipt under
+        if !package.is_stdin() {
+            manifest.package = Some(Manifest::package_name(package.path()));
+        }
+        if write_receipt && !package.is_stdin() {
+            let cwd = env::current_dir()?;
+            let receipt_path = Manifest::receipt_path(output_path.unwrap_or(&cwd), package.path());
+            manifest.save_receipt(&report, &receipt_path)?;
+            if format.is_text() {
+                println!("Receipt written to '{}'", receipt_path.display());
+            }
+        }
+        if let Some(manifest_path) = manifest_path {
+            manifest.save(Path::new(manifest_path))?;
+            if format.is_text() {
+                println!("Manifest written to '{}'", manifest_path);
+            }
+        }
+    }
+
+    if !format.is_text() {
+        return print_json_records(format, extract_report_json(&report));
+    }
+
+    print_summary(&report);
+
+    println!("--- Finished in {:.4} seconds ---", duration.as_secs_f64());
+    Ok(())
+}
+
+fn extract_package(
+    package: &UnityPackage,
+    output_path: Option<&Path>,
+    options: &ExtractOptions,
+    format: OutputFormat,
+    show_progress: bool,
+) -> Result<ExtractReport> {
+    // Determine output path (cwd by default)
+    let cwd = env::current_dir()?;
+    let output_path = output_path.unwrap_or(&cwd);
+
+    // Machine-readable formats print the whole manifest at the end instead
+    if !format.is_text() {
+        return package.extract(output_path, options);
+    }
+
+    if options.dry_run {
+        println!("Dry run: nothing will be written to '{}'.", output_path.display());
+    } else if options.atomic {
+        println!("Extracting package (changes are applied once everything has been read)...");
+    } else {
+        println!("Extracting package...");
+    }
+
+    let result = if show_progress {
+        // The progress line replaces the per-file lines; only warnings are still printed
+        let printer = RefCell::new(ProgressPrinter::new());
+        let result = package.extract_with_progress(
+            output_path,
+            options,
+            |record| {
+                if record.action == ExtractAction::SkippedOutsideDestination {
+                    printer.borrow_mut().clear();
+                    print_record(record, output_path, options.dry_run);
+                }
+            },
+            |progress| printer.borrow_mut().update(progress),
+        );
+        printer.into_inner().finish();
+        result
+    } else {
+        package.extract_with(output_path, options, |record| {
+            print_record(record, output_path, options.dry_run);
+        })
+    };
+    if result.is_err() && options.atomic && !options.dry_run {
+        println!("Rolled back: '{}' was left as it was before the extraction.", output_path.display());
+    }
+    result
+}
+
+/// Writes the selected assets to standard output as a tar named by pathname; warnings go to stderr
+fn write_tar(package: &UnityPackage, options: &ExtractOptions) -> Result<()> {
+    let report = package.write_tar(BufWriter::new(io::stdout().lock()), options)?;
+    for record in &report.records {
+        if record.action == ExtractAction::SkippedOutsideDestination {
+            eprintln!("WARNING: Skipping '{}' as '{}' is outside the archive.", record.guid, record.pathname);
+        }
+    }
+    Ok(())
+}
+
+/// Writes the selected assets to a zip file named by pathname, replacing it only once complete
+fn write_zip(
+    package: &UnityPackage,
+    zip_path: &Path,
+    options: &ExtractOptions,
+    previews: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let parent = zip_path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let staged = NamedTempFile::new_in(parent)
+        .with_context(|| format!("Could not create a temporary file in '{}'", parent.display()))?;
+
+    if format.is_text() {
+        println!("Writing package to zip...");
+    }
+    let report = package.write_zip(BufWriter::new(staged.as_file()), options, previews)?;
+    staged
+        .persist(zip_path)
+        .with_context(|| format!("Could not write '{}'", zip_path.display()))?;
+
+    if !format.is_text() {
+        return print_json_records(format, extract_report_json(&report));
+    }
+    for record in &report.records {
+        print_record(record, zip_path, false);
+    }
+    print_summary(&report);
+    println!("Zip written to '{}'", zip_path.display());
+    Ok(())
+}
+
+/// Number of writer threads given to `--jobs`
+pub fn parse_jobs(option: &str, value: Option<&String>) -> Result<usize> {
+    match option_value(option, value)?.parse() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => bail!("Error: Option '{}' expects a number of threads greater than 0.", option),
+    }
+}
+
+/// GUIDs of the requested assets (by pathname or GUID) and everything they reference
+fn dependency_closure(package: &UnityPackage, roots: &[&str], verbose: bool) -> Result<HashSet<String>> {
+    let graph = package.dependency_graph()?;
+
+    let mut guids = Vec::new();
+    for root in roots {
+        let guid = match graph.guid_of(root) {
+            Some(guid) => guid,
+            None if graph.pathname(root).is_some() => root,
+            None => bail!("Error: '{}' is not a pathname or GUID in the package.", root),
+        };
+        guids.push(guid);
+    }
+
+    let closure = graph.closure(guids);
+    if verbose {
+        println!("Selected {} entries needed by {}", closure.len(), roots.join(", "));
+    }
+    Ok(closure)
+}
+
+/// Prints one line per handled file, phrased as a plan for dry runs
+pub fn print_record(record: &ExtractRecord, output_path: &Path, dry_run: bool) {
+    let (guid, pathname) = (&record.guid, &record.pathname);
+    let would = |done: &str, planned: &str| if dry_run { planned.to_string() } else { done.to_string() };
+
+    match record.action {
+        ExtractAction::Extracted if record.kind == FileKind::Folder => {
+            println!("{} folder '{}' as '{}'", would("Creating", "Would create"), guid, pathname);
+        }
+        ExtractAction::Extracted => {
+            println!("{} '{}' as '{}'", would("Extracting", "Would extract"), guid, pathname);
+        }
+        ExtractAction::Overwritten => {
+            println!("{} '{}' as '{}'", would("Overwriting", "Would overwrite"), guid, pathname);
+        }
+        ExtractAction::KeptBoth => {
+            println!("{} '{}' as '{}' (kept the existing file)", would("Extracting", "Would extract"), guid, pathname);
+        }
+        ExtractAction::Unchanged => {
+            println!("Unchanged '{}' as '{}'", guid, pathname);
+        }
+        ExtractAction::SkippedExisting => {
+            println!("{} '{}' as '{}' already exists.", would("Skipping", "Would skip"), guid, pathname);
+        }
+        ExtractAction::Filtered => return,
+        ExtractAction::SkippedOutsideDestination => {
+            println!("WARNING: {} '{}' as '{}' is outside the destination path '{}'.",
+                would("Skipping", "Would skip"),
+                guid,
+                record.destination.display(),
+                output_path.display()
+            );
+        }
+    }
+
+    if let Some(original) = &record.renamed_from {
+        println!("    (renamed from '{}' to be a valid Windows filename)", original);
+    }
+}
+
+/// Prints how many files ended with each conflict outcome
+fn print_summary(report: &ExtractReport) {
+    if let Some(summary) = summarize(report) {
+        println!("Summary: {}", summary);
+    }
+}
+
+/// Counts of each conflict outcome, e.g. `12 new, 3 overwritten`, or `None` if nothing was handled
+pub fn summarize(report: &ExtractReport) -> Option<String> {
+    let counts = [
+        (ExtractAction::Extracted, "new"),
+        (ExtractAction::Overwritten, "overwritten"),
+        (ExtractAction::Unchanged, "unchanged"),
+        (ExtractAction::SkippedExisting, "skipped (already existed)"),
+        (ExtractAction::KeptBoth, "kept both"),
+    ];
+
+    let parts: Vec<String> = counts
+        .iter()
+        .map(|&(action, label)| (report.count(action), label))
+        .filter(|&(count, _)| count > 0)
+        .map(|(count, label)| format!("{} {}", count, label))
+        .collect();
+
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::Path;
+use unitypackage_extractor::{PathFilter, PreviewRecord, UnityPackage};
+
+use super::{format_size, option_value};
+
+/// `gallery <file.unitypackage> <output_dir> [--include <glob>]... [--exclude <glob>]...`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut positional = Vec::new();
+    let mut includes = Vec::new();
+    let mut excludes = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--include" => includes.push(option_value(arg, args.next())?),
+            "--exclude" => excludes.push(option_value(arg, args.next())?),
+            _ if arg.starts_with('-') => bail!("Error: Unknown option '{}' for gallery.", arg),
+            _ => positional.push(arg),
+        }
+    }
+
+    let [package_path, output_path] = positional.as_slice() else {
+        bail!("Error: Usage: gallery <file.unitypackage> <output_dir>");
+    };
+    let output_path = Path::new(output_path);
+    let filter = PathFilter::new(includes, excludes)?;
+
+    let package = UnityPackage::open(Path::new(package_path))?;
+    fs::create_dir_all(output_path)
+        .with_context(|| format!("Could not create folder '{}'", output_path.display()))?;
+    let records = package.extract_previews(output_path, &filter)?;
+
+    let title = package.path().file_name().unwrap_or_default().to_string_lossy();
+    let index_path = output_path.join("index.html");
+    fs::write(&index_path, render_index(&title, &records))
+        .with_context(|| format!("Could not write '{}'", index_path.display()))?;
+
+    let previews = records.iter().filter(|r| r.preview.is_some()).count();
+    println!("{} assets, {} with a preview", records.len(), previews);
+    println!("Gallery written to '{}'", index_path.display());
+    Ok(())
+}
+
+/// Static page with one card per asset: thumbnail, pathname, size and type
+fn render_index(title: &str, records: &[PreviewRecord]) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+    html.push_str(
+        "<style>\n\
+         body { font-family: sans-serif; margin: 2em; background: #f4f4f4; }\n\
+         .grid { display: grid; grid-template-columns: repeat(auto-fill, minmax(160px, 1fr)); gap: 1em; }\n\
+         .card { background: #fff; border-radius: 4px; padding: 0.5em; box-shadow: 0 1px 3px #0003; }\n\
+         .thumb { height: 128px; display: flex; align-items: center; justify-content: center; background: #e8e8e8; }\n\
+         .thumb img { max-width: 128px; max-height: 128px; }\n\
+         .none { color: #999; font-size: 0.8em; }\n\
+         .path { font-size: 0.8em; word-break: break-all; margin-top: 0.5em; }\n\
+         .info { font-size: 0.75em; color: #666; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    html.push_str(&format!("<h1>{}</h1>\n<p>{} assets</p>\n<div class=\"grid\">\n", escape_html(title), records.len()));
+
+    for record in records {
+        let thumb = match &record.preview {
+            Some(preview) => format!("<img src=\"{}\" alt=\"\" loading=\"lazy\">", escape_html(&url_path(preview))),
+            None => "<span class=\"none\">No preview</span>".to_string(),
+        };
+        html.push_str(&format!(
+            "<div class=\"card\" title=\"{guid}\"><div class=\"thumb\">{thumb}</div>\
+             <div class=\"path\">{pathname}</div><div class=\"info\">{kind} &middot; {size}</div></div>\n",
+            guid = escape_html(&record.guid),
+            thumb = thumb,
+            pathname = escape_html(&record.pathname),
+            kind = asset_type(&record.pathname),
+            size = format_size(record.size),
+        ));
+    }
+
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
+
+/// Unity asset type guessed from the file extension
+fn asset_type(pathname: &str) -> String {
+    let extension = match pathname.rsplit_once('.') {
+        Some((stem, ext)) if !stem.ends_with('/') => ext.to_ascii_lowercase(),
+        _ => return "File".to_string(),
+    };
+
+    let kind = match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "tga" | "psd" | "tif" | "tiff" | "exr" | "hdr" | "gif" | "bmp" => "Texture",
+        "fbx" | "obj" | "blend" | "dae" | "3ds" | "max" => "Model",
+        "prefab" => "Prefab",
+        "unity" => "Scene",
+        "mat" => "Material",
+        "shader" | "shadergraph" | "hlsl" | "cginc" | "compute" => "Shader",
+        "cs" => "Script",
+        "anim" => "Animation",
+        "controller" | "overridecontroller" => "Animator Controller",
+        "asset" => "Asset",
+        "wav" | "mp3" | "ogg" | "aif" | "aiff" => "Audio",
+        "ttf" | "otf" => "Font",
+        "txt" | "json" | "xml" | "md" | "bytes" => "Text",
+        "dll" | "so" | "dylib" => "Plugin",
+        _ => return extension.to_ascii_uppercase(),
+    };
+    kind.to_string()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Percent-encodes a relative pathname for use in a URL, keeping the `/` separators
+fn url_path(pathname: &str) -> String {
+    let mut url = String::new();
+    for byte in pathname.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    url
+}
@@ -0,0 +1,116 @@
+use anyhow::{Result, bail};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use unitypackage_extractor::PackageEntry;
+
+use super::output::{OutputFormat, entry_json, print_json_records};
+use super::{format_size, open_package, option_value};
+
+/// `list <file.unitypackage|-> [--tree] [--format <text|json|ndjson>]`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut package_path = None;
+    let mut tree = false;
+    let mut format = OutputFormat::Text;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tree" => tree = true,
+            "--flat" => tree = false,
+            "--format" => format = option_value(arg, args.next())?.parse()?,
+            _ if arg.starts_with('-') && arg != "-" => bail!("Error: Unknown option '{}' for list.", arg),
+            _ if package_path.is_none() => package_path = Some(arg),
+            _ => bail!("Error: Unexpected argument '{}' for list.", arg),
+        }
+    }
+
+    let Some(package_path) = package_path else {
+        bail!("Error: You must specify the .unitypackage file to list.");
+    };
+
+    let package = open_package(package_path)?;
+    let entries = package.entries()?;
+
+    if !format.is_text() {
+        let records = entries.iter().map(|e| Value::Object(entry_json(e))).collect();
+        return print_json_records(format, records);
+    }
+
+    if tree {
+        print_tree(&entries);
+    } else {
+        print_flat(&entries);
+    }
+
+    let total: u64 = entries.iter().map(|e| e.size).sum();
+    println!();
+    println!("{} entries, {}", entries.len(), format_size(total));
+    Ok(())
+}
+
+/// Short flag column: `A` asset, `M` meta, `P` preview, `-` when missing
+fn flags(entry: &PackageEntry) -> String {
+    [
+        (entry.has_asset, 'A'),
+        (entry.has_meta, 'M'),
+        (entry.has_preview, 'P'),
+    ]
+    .iter()
+    .map(|&(present, flag)| if present { flag } else { '-' })
+    .collect()
+}
+
+fn print_flat(entries: &[PackageEntry]) {
+    println!("{:<32}  {:>10}  {:<5}  PATHNAME", "GUID", "SIZE", "FLAGS");
+    for entry in entries {
+        println!(
+            "{:<32}  {:>10}  {:<5}  {}",
+            entry.guid,
+            format_size(entry.size),
+            flags(entry),
+            entry.pathname
+        );
+    }
+}
+
+/// Directory node of the tree view, keyed by path component
+#[derive(Default)]
+struct Node<'a> {
+    entry: Option<&'a PackageEntry>,
+    children: BTreeMap<&'a str, Node<'a>>,
+}
+
+fn print_tree(entries: &[PackageEntry]) {
+    let mut root = Node::default();
+    for entry in entries {
+        let mut node = &mut root;
+        for part in entry.pathname.split('/').filter(|p| !p.is_empty()) {
+            node = node.children.entry(part).or_default();
+        }
+        node.entry = Some(entry);
+    }
+
+    print_children(&root, "");
+}
+
+fn print_children(node: &Node, prefix: &str) {
+    let count = node.children.len();
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        let branch = if last { "└── " } else { "├── " };
+
+        let details = match child.entry {
+            Some(entry) if entry.has_asset => {
+                format!("  ({}, {}, {})", format_size(entry.size), entry.guid, flags(entry))
+            }
+            Some(entry) => format!("  ({}, {})", entry.guid, flags(entry)),
+            None => String::new(),
+        };
+        let is_dir = !child.children.is_empty() || child.entry.is_some_and(|e| e.is_folder);
+        let slash = if is_dir { "/" } else { "" };
+        println!("{}{}{}{}{}", prefix, branch, name, slash, details);
+
+        let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        print_children(child, &child_prefix);
+    }
+}
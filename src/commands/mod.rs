@@ -0,0 +1,53 @@
+//! Implementation of each command-line subcommand
+
+pub mod batch;
+pub mod cat;
+pub mod deps;
+pub mod diff;
+pub mod extract;
+pub mod gallery;
+pub mod list;
+pub mod output;
+pub mod pack;
+pub mod progress;
+pub mod uninstall;
+pub mod upgrade;
+pub mod verify;
+
+use anyhow::{Result, bail};
+use unitypackage_extractor::UnityPackage;
+
+/// Returns the value following an option such as `--include <glob>`
+pub fn option_value<'a>(option: &str, value: Option<&'a String>) -> Result<&'a str> {
+    match value {
+        Some(value) => Ok(value),
+        None => bail!("Error: Option '{}' requires a value.", option),
+    }
+}
+
+/// Opens the package at `path`, or reads it from standard input for `-`
+pub fn open_package(path: &str) -> Result<UnityPackage> {
+    if path == "-" {
+        Ok(UnityPackage::stdin())
+    } else {
+        UnityPackage::open(path)
+    }
+}
+
+/// Formats a byte count with a binary unit (e.g. `1.5 MiB`)
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{Result, bail};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::str::FromStr;
+use unitypackage_extractor::{ExtractRecord, ExtractReport, FileKind, PackageEntry};
+
+/// How command results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// A single JSON array with one object per entry
+    Json,
+    /// One JSON object per line (newline-delimited JSON)
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn is_text(self) -> bool {
+        self == OutputFormat::Text
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            _ => bail!("Error: Unknown format '{}'. Expected text, json or ndjson.", s),
+        }
+    }
+}
+
+/// Prints machine-readable records in the requested format
+pub fn print_json_records(format: OutputFormat, records: Vec<Value>) -> Result<()> {
+    match format {
+        OutputFormat::Text => {}
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&Value::Array(records))?),
+        OutputFormat::Ndjson => {
+            for record in records {
+                println!("{}", serde_json::to_string(&record)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Manifest record of a package entry
+pub fn entry_json(entry: &PackageEntry) -> Map<String, Value> {
+    let mut map = Map::new();
+    map.insert("guid".into(), json!(entry.guid));
+    map.insert("pathname".into(), json!(entry.pathname));
+    map.insert("size".into(), json!(entry.size));
+    map.insert("has_asset".into(), json!(entry.has_asset));
+    map.insert("has_meta".into(), json!(entry.has_meta));
+    map.insert("has_preview".into(), json!(entry.has_preview));
+    map.insert("is_folder".into(), json!(entry.is_folder));
+    map
+}
+
+/// Manifest records of an extraction: each entry with the actions taken on its asset/folder and meta
+pub fn extract_report_json(report: &ExtractReport) -> Vec<Value> {
+    // Grouped once, as looking each entry up in all records is too slow for large packages
+    let mut by_guid: HashMap<&str, Vec<&ExtractRecord>> = HashMap::new();
+    for record in &report.records {
+        by_guid.entry(record.guid.as_str()).or_default().push(record);
+    }
+
+    report
+        .entries
+        .iter()
+        .map(|entry| {
+            let mut map = entry_json(entry);
+            let records = by_guid.get(entry.guid.as_str()).map(Vec::as_slice).unwrap_or_default();
+            let main = records.iter().copied().find(|r| r.kind != FileKind::Meta);
+            let meta = records.iter().copied().find(|r| r.kind == FileKind::Meta);
+
+            map.insert("action".into(), action_json(main));
+            map.insert("meta_action".into(), action_json(meta));
+            if let Some(record) = main.or(meta) {
+                map.insert("destination".into(), json!(record.destination.to_string_lossy()));
+            }
+            if let Some(original) = main.and_then(|r| r.renamed_from.as_ref()) {
+                map.insert("renamed_from".into(), json!(original));
+            }
+            Value::Object(map)
+        })
+        .collect()
+}
+
+fn action_json(record: Option<&ExtractRecord>) -> Value {
+    match record {
+        Some(record) => json!(record.action.as_str()),
+        None => Value::Null,
+    }
+}
@@ -0,0 +1,34 @@
+use anyhow::{Result, bail};
+use std::path::Path;
+use std::time::Instant;
+use unitypackage_extractor::pack::SkipReason;
+use unitypackage_extractor::pack_directory;
+
+/// `pack <folder> <output.unitypackage>`
+pub fn run(args: &[String]) -> Result<()> {
+    let [source, output] = args else {
+        bail!("Error: Usage: pack <folder> <output.unitypackage>");
+    };
+
+    let start_time = Instant::now();
+    println!("Packing '{}'...", source);
+    let report = pack_directory(Path::new(source), Path::new(output))?;
+
+    for entry in &report.packed {
+        let suffix = if entry.is_folder { "/" } else { "" };
+        println!("Adding '{}' as '{}{}'", entry.guid, entry.pathname, suffix);
+    }
+
+    for skipped in &report.skipped {
+        let reason = match &skipped.reason {
+            SkipReason::MissingMeta => "it has no .meta file".to_string(),
+            SkipReason::InvalidMeta => "its .meta file has no valid GUID".to_string(),
+            SkipReason::DuplicateGuid { other } => format!("its GUID is already used by '{}'", other),
+        };
+        println!("WARNING: Skipping '{}' as {}.", skipped.path.display(), reason);
+    }
+
+    println!("Packed {} entries into '{}'", report.packed.len(), output);
+    println!("--- Finished in {:.4} seconds ---", start_time.elapsed().as_secs_f64());
+    Ok(())
+}
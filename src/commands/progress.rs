@@ -0,0 +1,106 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+use unitypackage_extractor::Progress;
+
+use super::format_size;
+
+/// Redraw interval when stdout is a terminal
+const TERMINAL_INTERVAL: Duration = Duration::from_millis(100);
+/// Interval between plain lines when stdout is redirected (logs, CI)
+const PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Prints [`Progress`] reports: a single line redrawn in place on a terminal,
+/// or a plain line every few seconds otherwise
+pub struct ProgressPrinter {
+    terminal: bool,
+    last_print: Option<Instant>,
+    last: Option<Progress>,
+    /// A progress line is on screen without a newline after it
+    drawn: bool,
+}
+
+impl ProgressPrinter {
+    pub fn new() -> Self {
+        Self {
+            terminal: io::stdout().is_terminal(),
+            last_print: None,
+            last: None,
+            drawn: false,
+        }
+    }
+
+    pub fn update(&mut self, progress: &Progress) {
+        self.last = Some(*progress);
+        let interval = if self.terminal { TERMINAL_INTERVAL } else { PLAIN_INTERVAL };
+        if self.last_print.is_some_and(|last| last.elapsed() < interval) {
+            return;
+        }
+        self.last_print = Some(Instant::now());
+        self.print(progress);
+    }
+
+    /// Removes the progress line so other output can be printed
+    pub fn clear(&mut self) {
+        if self.drawn {
+            print!("\r\x1b[K");
+            let _ = io::stdout().flush();
+            self.drawn = false;
+        }
+    }
+
+    /// Prints the last report, so the final state is always shown
+    pub fn finish(mut self) {
+        if let Some(progress) = self.last {
+            self.print(&progress);
+        }
+        if self.drawn {
+            println!();
+        }
+    }
+
+    fn print(&mut self, progress: &Progress) {
+        let line = format_progress(progress);
+        if self.terminal {
+            print!("\r\x1b[K{}", line);
+            let _ = io::stdout().flush();
+            self.drawn = true;
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// `42.0% | 1.2 MiB / 2.9 MiB | 1234 entries | 3.4 MiB/s | ETA 0:05`, or
+/// `1.2 MiB read | 1234 entries | 3.4 MiB/s` when the package size is unknown (standard input)
+fn format_progress(progress: &Progress) -> String {
+    let entries = progress.entries;
+    let throughput = format_size(progress.throughput() as u64);
+
+    let (Some(fraction), Some(total_bytes)) = (progress.fraction(), progress.total_bytes) else {
+        return format!("{} read | {} entries | {}/s", format_size(progress.bytes_read), entries, throughput);
+    };
+    let eta = match progress.eta() {
+        Some(eta) => format_duration(eta),
+        None => "--:--".to_string(),
+    };
+    format!(
+        "{:5.1}% | {} / {} | {} entries | {}/s | ETA {}",
+        fraction * 100.0,
+        format_size(progress.bytes_read),
+        format_size(total_bytes),
+        entries,
+        throughput,
+        eta
+    )
+}
+
+/// `m:ss`, or `h:mm:ss` past an hour
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use unitypackage_extractor::{
+    FileKind, Manifest, RECEIPT_FOLDER, UninstallAction, UninstallOptions, UninstallReport, uninstall,
+};
+
+use super::option_value;
+use super::output::{OutputFormat, print_json_records};
+
+/// `uninstall <file.unitypackage|receipt.json> [output_path] [--force] [--dry-run] [--format <text|json|ndjson>]`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut positional = Vec::new();
+    let mut format = OutputFormat::Text;
+    let mut options = UninstallOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--force" => options.force = true,
+            "--dry-run" => options.dry_run = true,
+            "--format" => format = option_value(arg, args.next())?.parse()?,
+            _ if arg.starts_with('-') => bail!("Error: Unknown option '{}' for uninstall.", arg),
+            _ => positional.push(arg),
+        }
+    }
+
+    let Some(package) = positional.first() else {
+        bail!("Error: You must specify the package (or its receipt) to uninstall.");
+    };
+    if positional.len() > 2 {
+        bail!("Error: Unexpected argument '{}' for uninstall.", positional[2]);
+    }
+
+    let cwd = env::current_dir()?;
+    let output_path = positional.get(1).map(Path::new).unwrap_or(&cwd);
+    let receipt_path = find_receipt(Path::new(package), output_path)?;
+    let receipt = Manifest::load(&receipt_path)?;
+
+    if format.is_text() && options.dry_run {
+        println!("Dry run: nothing will be removed from '{}'.", output_path.display());
+    }
+
+    let report = uninstall(output_path, &receipt, &options)?;
+
+    if !options.dry_run {
+        if report.remaining.files.is_empty() {
+            fs::remove_file(&receipt_path)?;
+            // Ignored when other receipts are still there
+            let _ = fs::remove_dir(output_path.join(RECEIPT_FOLDER));
+        } else {
+            report.remaining.save(&receipt_path)?;
+        }
+    }
+
+    if format.is_text() {
+        print_text(&report, options.dry_run);
+    } else {
+        let records = report
+            .records
+            .iter()
+            .map(|record| {
+                json!({
+                    "guid": record.guid,
+                    "kind": record.kind.as_str(),
+                    "pathname": record.pathname,
+                    "action": record.action.as_str(),
+                })
+            })
+            .collect::<Vec<Value>>();
+        print_json_records(format, records)?;
+    }
+
+    let kept = report.count(UninstallAction::KeptModified);
+    if kept > 0 {
+        bail!("Error: {} modified file(s) were kept. Use --force to remove them too.", kept);
+    }
+    Ok(())
+}
+
+/// The receipt itself, or the one kept in the output directory for a package name
+fn find_receipt(package: &Path, output_path: &Path) -> Result<PathBuf> {
+    if package.extension().is_some_and(|ext| ext == "json") && package.is_file() {
+        return Ok(package.to_path_buf());
+    }
+
+    let receipt_path = Manifest::receipt_path(output_path, package);
+    if !receipt_path.is_file() {
+        bail!(
+            "Error: No receipt for '{}' in '{}'. Was it extracted there?",
+            package.display(),
+            output_path.join(RECEIPT_FOLDER).display()
+        );
+    }
+    Ok(receipt_path)
+}
+
+fn print_text(report: &UninstallReport, dry_run: bool) {
+    let would = |done: &str, planned: &str| if dry_run { planned.to_string() } else { done.to_string() };
+
+    for record in &report.records {
+        let pathname = &record.pathname;
+        match record.action {
+            UninstallAction::Removed if record.kind == FileKind::Folder => {
+                println!("{} folder '{}'", would("Removing", "Would remove"), pathname);
+            }
+            UninstallAction::Removed => println!("{} '{}'", would("Removing", "Would remove"), pathname),
+            UninstallAction::RemovedModified => {
+                println!("{} '{}' (modified since extraction)", would("Removing", "Would remove"), pathname);
+            }
+            UninstallAction::KeptModified => {
+                println!("{} '{}': it was modified since extraction.", would("Keeping", "Would keep"), pathname);
+            }
+            UninstallAction::KeptNotEmpty => {
+                println!("{} folder '{}': it is not empty.", would("Keeping", "Would keep"), pathname);
+            }
+            UninstallAction::Missing => {}
+            UninstallAction::SkippedOutsideDestination => {
+                println!("WARNING: Skipping '{}', it is outside the output path.", pathname);
+            }
+        }
+    }
+
+    println!(
+        "Summary: {} removed, {} kept, {} already missing",
+        report.count(UninstallAction::Removed) + report.count(UninstallAction::RemovedModified),
+        report.count(UninstallAction::KeptModified) + report.count(UninstallAction::KeptNotEmpty),
+        report.count(UninstallAction::Missing)
+    );
+}
@@ -0,0 +1,166 @@
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+use std::env;
+use std::fs;
+use std::path::Path;
+use unitypackage_extractor::{Manifest, UnityPackage, UpgradeAction, UpgradeOptions, UpgradeRecord, UpgradeReport};
+
+use super::option_value;
+use super::output::{OutputFormat, print_json_records};
+
+/// `upgrade <new.unitypackage> [output_path] --manifest <file> [--no-meta] [--dry-run] [--format <text|json|ndjson>]`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut positional = Vec::new();
+    let mut manifest_path = None;
+    let mut format = OutputFormat::Text;
+    let mut options = UpgradeOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--manifest" => manifest_path = Some(option_value(arg, args.next())?),
+            "--no-meta" => options.extract_meta = false,
+            "--dry-run" => options.dry_run = true,
+            "--format" => format = option_value(arg, args.next())?.parse()?,
+            _ if arg.starts_with('-') => bail!("Error: Unknown option '{}' for upgrade.", arg),
+            _ => positional.push(arg),
+        }
+    }
+
+    let Some(package_path) = positional.first() else {
+        bail!("Error: You must specify the new .unitypackage file.");
+    };
+    if positional.len() > 2 {
+        bail!("Error: Unexpected argument '{}' for upgrade.", positional[2]);
+    }
+    let Some(manifest_path) = manifest_path.map(Path::new) else {
+        bail!("Error: upgrade needs the --manifest written by the previous extraction.");
+    };
+
+    let package = UnityPackage::open(Path::new(package_path))?;
+    let previous = Manifest::load(manifest_path)?;
+
+    let cwd = env::current_dir()?;
+    let output_path = positional.get(1).map(Path::new).unwrap_or(&cwd);
+
+    if format.is_text() && options.dry_run {
+        println!("Dry run: nothing will be written to '{}'.", output_path.display());
+    }
+
+    let mut report = package.upgrade(output_path, &previous, &options)?;
+    if !options.dry_run {
+        report.manifest.package = Some(Manifest::package_name(package.path()));
+        let receipt_path = Manifest::receipt_path(output_path, package.path());
+        report.manifest.save(&receipt_path)?;
+
+        // The receipt of the previous version, and the manifest if it is one, are superseded
+        // by the one just written
+        let mut superseded = Vec::new();
+        if manifest_path.parent() == receipt_path.parent() {
+            superseded.push(manifest_path.to_path_buf());
+        } else {
+            report.manifest.save(manifest_path)?;
+        }
+        if let Some(name) = &previous.package {
+            superseded.push(Manifest::named_receipt_path(output_path, name));
+        }
+        for path in superseded {
+            if path != receipt_path && path.is_file() {
+                fs::remove_file(&path).with_context(|| format!("Could not remove receipt '{}'", path.display()))?;
+            }
+        }
+    }
+
+    if format.is_text() {
+        for record in &report.records {
+            print_record(record, output_path, options.dry_run);
+        }
+        print_summary(&report);
+    } else {
+        print_json_records(format, report.records.iter().map(record_json).collect())?;
+    }
+
+    let conflicts = report.conflicts().count();
+    if conflicts > 0 {
+        bail!("Error: {} file(s) were left as they are and need to be merged manually.", conflicts);
+    }
+    Ok(())
+}
+
+fn print_record(record: &UpgradeRecord, output_path: &Path, dry_run: bool) {
+    let (guid, pathname) = (&record.guid, &record.pathname);
+    let would = |done: &str, planned: &str| if dry_run { planned.to_string() } else { done.to_string() };
+
+    match record.action {
+        UpgradeAction::Added => println!("{} '{}' as '{}'", would("Adding", "Would add"), guid, pathname),
+        UpgradeAction::Updated => println!("{} '{}' as '{}'", would("Updating", "Would update"), guid, pathname),
+        UpgradeAction::Unchanged => {
+            if let Some(previous) = &record.moved_from {
+                println!("{} '{}' to '{}'", would("Moving", "Would move"), previous, pathname);
+            }
+            return;
+        }
+        UpgradeAction::KeptLocal => println!("Keeping local changes to '{}'", pathname),
+        UpgradeAction::Removed => println!("{} '{}' ('{}')", would("Removing", "Would remove"), pathname, guid),
+        UpgradeAction::DeletedLocally => println!("Not restoring '{}', it was deleted locally", pathname),
+        UpgradeAction::Conflict(conflict) => println!("CONFLICT: '{}' was {}.", pathname, conflict.description()),
+        UpgradeAction::SkippedOutsideDestination => {
+            println!("WARNING: {} '{}' as '{}' is outside the destination path '{}'.",
+                would("Skipping", "Would skip"),
+                guid,
+                record.destination.display(),
+                output_path.display()
+            );
+        }
+    }
+
+    if let Some(previous) = &record.moved_from {
+        println!("    (moved from '{}')", previous);
+    }
+}
+
+fn print_summary(report: &UpgradeReport) {
+    let counts = [
+        (UpgradeAction::Added, "added"),
+        (UpgradeAction::Updated, "updated"),
+        (UpgradeAction::KeptLocal, "kept with local changes"),
+        (UpgradeAction::Removed, "removed"),
+    ];
+
+    let mut parts: Vec<String> = counts
+        .iter()
+        .map(|&(action, label)| (report.count(action), label))
+        .filter(|&(count, _)| count > 0)
+        .map(|(count, label)| format!("{} {}", count, label))
+        .collect();
+
+    let moved = report.records.iter().filter(|r| r.moved_from.is_some()).count();
+    if moved > 0 {
+        parts.push(format!("{} moved", moved));
+    }
+    let conflicts = report.conflicts().count();
+    if conflicts > 0 {
+        parts.push(format!("{} conflicts", conflicts));
+    }
+
+    if parts.is_empty() {
+        println!("Already up to date.");
+    } else {
+        println!("Summary: {}", parts.join(", "));
+    }
+}
+
+fn record_json(record: &UpgradeRecord) -> Value {
+    let conflict = match record.action {
+        UpgradeAction::Conflict(conflict) => json!(conflict.as_str()),
+        _ => Value::Null,
+    };
+    json!({
+        "guid": record.guid,
+        "kind": record.kind.as_str(),
+        "pathname": record.pathname,
+        "moved_from": record.moved_from,
+        "action": record.action.as_str(),
+        "conflict": conflict,
+    })
+}
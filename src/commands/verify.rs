@@ -0,0 +1,64 @@
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+use unitypackage_extractor::VerifyReport;
+
+use super::{open_package, option_value};
+use super::output::{OutputFormat, print_json_records};
+
+/// `verify <file.unitypackage|-> [--format <text|json|ndjson>]`
+pub fn run(args: &[String]) -> Result<()> {
+    let mut package_path = None;
+    let mut format = OutputFormat::Text;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = option_value(arg, args.next())?.parse()?,
+            _ if arg.starts_with('-') && arg != "-" => bail!("Error: Unknown option '{}' for verify.", arg),
+            _ if package_path.is_none() => package_path = Some(arg),
+            _ => bail!("Error: Unexpected argument '{}' for verify.", arg),
+        }
+    }
+
+    let Some(package_path) = package_path else {
+        bail!("Error: You must specify the .unitypackage file to verify.");
+    };
+
+    let report = open_package(package_path)?.verify()?;
+
+    if format.is_text() {
+        print_text(&report);
+    } else {
+        let records: Vec<Value> = report
+            .issues
+            .iter()
+            .map(|issue| {
+                json!({
+                    "category": issue.category.as_str(),
+                    "guid": issue.guid,
+                    "message": issue.message,
+                })
+            })
+            .collect();
+        print_json_records(format, records)?;
+    }
+
+    if !report.is_ok() {
+        bail!("Error: '{}' failed verification with {} issue(s).", package_path, report.issues.len());
+    }
+    Ok(())
+}
+
+fn print_text(report: &VerifyReport) {
+    for (category, issues) in report.by_category() {
+        println!("{} ({}):", category.description(), issues.len());
+        for issue in issues {
+            println!("  {}", issue.message);
+        }
+        println!();
+    }
+
+    if report.is_ok() {
+        println!("OK: {} entries, no issues found.", report.entries);
+    }
+}
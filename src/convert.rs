@@ -0,0 +1,293 @@
+use anyhow::{Context, Result};
+use path_clean::PathClean;
+use std::collections::HashSet;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+use crate::extract::{ExtractAction, ExtractOptions, ExtractRecord, ExtractReport, FileKind, OutputResolver, Resolved};
+use crate::package::PackageArchive;
+use crate::stream::{FileInfo, PackageVisitor, visit_package};
+
+/// Pathnames are resolved under this (never created) root, so the same traversal check as
+/// extraction rejects anything that would end up outside the archive
+const ARCHIVE_ROOT: &str = "/unitypackage";
+
+/// Archive format a package can be converted to, with entries named by real pathname
+pub(crate) trait ArchiveWriter {
+    /// Adds a folder entry, returning `false` if the format cannot hold it twice and it already exists
+    fn add_folder(&mut self, name: &str, mtime: u64) -> Result<bool>;
+    /// Adds a file of `size` bytes read from `data`, returning `false` if the format cannot
+    /// hold it twice and it already exists
+    fn add_file(&mut self, name: &str, size: u64, mtime: u64, data: &mut dyn Read) -> Result<bool>;
+}
+
+/// Writes a plain tar stream
+pub(crate) struct TarWriter<W: Write> {
+    builder: tar::Builder<W>,
+}
+
+impl<W: Write> TarWriter<W> {
+    pub(crate) fn new(out: W) -> Self {
+        Self {
+            builder: tar::Builder::new(out),
+        }
+    }
+
+    /// Writes the end-of-archive marker
+    pub(crate) fn finish(self) -> Result<()> {
+        let mut out = self.builder.into_inner().context("Could not write the tar stream")?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> ArchiveWriter for TarWriter<W> {
+    fn add_folder(&mut self, name: &str, mtime: u64) -> Result<bool> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(0);
+        header.set_mode(0o755);
+        header.set_mtime(mtime);
+        header.set_entry_type(tar::EntryType::Directory);
+        self.builder
+            .append_data(&mut header, format!("{}/", name), io::empty())
+            .with_context(|| format!("Could not add '{}' to the tar stream", name))?;
+        Ok(true)
+    }
+
+    fn add_file(&mut self, name: &str, size: u64, mtime: u64, data: &mut dyn Read) -> Result<bool> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_entry_type(tar::EntryType::Regular);
+        self.builder
+            .append_data(&mut header, name, data)
+            .with_context(|| format!("Could not add '{}' to the tar stream", name))?;
+        Ok(true)
+    }
+}
+
+/// Writes a deflate-compressed zip archive
+pub(crate) struct ZipOutput<W: Write + Seek> {
+    zip: ZipWriter<W>,
+    /// Zip entries must be unique, unlike tar entries
+    names: HashSet<String>,
+}
+
+impl<W: Write + Seek> ZipOutput<W> {
+    pub(crate) fn new(out: W) -> Self {
+        Self {
+            zip: ZipWriter::new(out),
+            names: HashSet::new(),
+        }
+    }
+
+    /// Writes the central directory
+    pub(crate) fn finish(self) -> Result<()> {
+        let mut out = self.zip.finish().context("Could not write the zip archive")?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn options(mtime: u64) -> SimpleFileOptions {
+        SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(zip_time(mtime))
+    }
+}
+
+impl<W: Write + Seek> ArchiveWriter for ZipOutput<W> {
+    fn add_folder(&mut self, name: &str, mtime: u64) -> Result<bool> {
+        let name = format!("{}/", name);
+        if !self.names.insert(name.clone()) {
+            return Ok(false);
+        }
+        self.zip
+            .add_directory(name.as_str(), Self::options(mtime))
+            .with_context(|| format!("Could not add '{}' to the zip archive", name))?;
+        Ok(true)
+    }
+
+    fn add_file(&mut self, name: &str, size: u64, mtime: u64, data: &mut dyn Read) -> Result<bool> {
+        if !self.names.insert(name.to_string()) {
+            return Ok(false);
+        }
+        let options = Self::options(mtime).large_file(size >= u32::MAX as u64);
+        self.zip
+            .start_file(name, options)
+            .with_context(|| format!("Could not add '{}' to the zip archive", name))?;
+        io::copy(data, &mut self.zip).with_context(|| format!("Could not add '{}' to the zip archive", name))?;
+        Ok(true)
+    }
+}
+
+/// Zip (MS-DOS) timestamp of a Unix time, clamped to the 1980-2107 range the format supports
+fn zip_time(mtime: u64) -> DateTime {
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let days = (mtime / 86_400) as i64;
+    let seconds = mtime % 86_400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let Ok(year) = u16::try_from(year.clamp(1980, 2107)) else {
+        return DateTime::default();
+    };
+    DateTime::from_date_and_time(
+        year,
+        month,
+        day,
+        (seconds / 3600) as u8,
+        (seconds / 60 % 60) as u8,
+        (seconds % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+struct Converter<'a, W> {
+    options: &'a ExtractOptions,
+    /// Also write `preview.png` as `<pathname>.png`
+    previews: bool,
+    resolver: OutputResolver,
+    writer: &'a mut W,
+    report: ExtractReport,
+}
+
+impl<W: ArchiveWriter> Converter<'_, W> {
+    /// Archive entry name of an output pathname, or `None` if it escapes the archive root
+    fn entry_name(&self, pathname: &str) -> (Option<String>, Resolved) {
+        let resolved = self.resolver.resolve(pathname);
+        if !resolved.inside {
+            return (None, resolved);
+        }
+
+        let relative = match resolved.destination.clean().strip_prefix(ARCHIVE_ROOT) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => return (None, resolved),
+        };
+        let parts: Vec<String> = relative
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        let name = (!parts.is_empty()).then(|| parts.join("/"));
+        (name, resolved)
+    }
+
+    fn add_file(
+        &mut self,
+        guid: &str,
+        kind: FileKind,
+        pathname: &str,
+        selected: bool,
+        info: FileInfo,
+        data: &mut dyn Read,
+    ) -> Result<()> {
+        let (name, resolved) = self.entry_name(&kind.output_pathname(pathname));
+        let action = match &name {
+            _ if !selected => ExtractAction::Filtered,
+            None => ExtractAction::SkippedOutsideDestination,
+            Some(name) => {
+                if self.writer.add_file(name, info.size, info.mtime, data)? {
+                    ExtractAction::Extracted
+                } else {
+                    ExtractAction::SkippedExisting
+                }
+            }
+        };
+        self.record(guid, kind, resolved, name, action);
+        Ok(())
+    }
+
+    /// Records a file, its destination being its entry name (or its pathname if it has none)
+    fn record(&mut self, guid: &str, kind: FileKind, resolved: Resolved, name: Option<String>, action: ExtractAction) {
+        let destination = PathBuf::from(name.as_deref().unwrap_or(&resolved.pathname));
+        self.report.records.push(ExtractRecord {
+            guid: guid.to_string(),
+            kind,
+            pathname: resolved.pathname,
+            renamed_from: resolved.renamed_from,
+            destination,
+            action,
+            hash: None,
+        });
+    }
+}
+
+impl<W: ArchiveWriter> PackageVisitor for Converter<'_, W> {
+    fn asset(&mut self, guid: &str, pathname: &str, selected: bool, info: FileInfo, data: &mut dyn Read) -> Result<()> {
+        self.add_file(guid, FileKind::Asset, pathname, selected, info, data)
+    }
+
+    /// Adds the folder entry and the `.meta`
+    fn meta(&mut self, guid: &str, pathname: &str, selected: bool, is_folder: bool, info: FileInfo, meta: &[u8]) -> Result<()> {
+        if is_folder {
+            let (name, resolved) = self.entry_name(pathname);
+            let action = match &name {
+                _ if !selected => ExtractAction::Filtered,
+                None => ExtractAction::SkippedOutsideDestination,
+                Some(name) => {
+                    if self.writer.add_folder(name, info.mtime)? {
+                        ExtractAction::Extracted
+                    } else {
+                        ExtractAction::SkippedExisting
+                    }
+                }
+            };
+            self.record(guid, FileKind::Folder, resolved, name, action);
+        }
+        if self.options.extract_meta {
+            self.add_file(guid, FileKind::Meta, pathname, selected, info, &mut &meta[..])?;
+        }
+        Ok(())
+    }
+
+    /// Adds a thumbnail as `<pathname>.png`, left out if an asset already has that name
+    fn preview(&mut self, _guid: &str, pathname: &str, selected: bool, info: FileInfo, data: &mut dyn Read) -> Result<()> {
+        let (name, _) = self.entry_name(&format!("{}.png", pathname));
+        if let Some(name) = name
+            && selected
+        {
+            self.writer.add_file(&name, info.size, info.mtime, data)?;
+        }
+        Ok(())
+    }
+
+    fn wants_previews(&self) -> bool {
+        self.previews
+    }
+}
+
+/// Streams the archive once, re-emitting assets (and metas, previews) named by their real pathname
+///
+/// Selection, sanitization and the path traversal check are the same as for extraction;
+/// conflict policies do not apply since the target archive starts empty.
+pub(crate) fn convert_archive<W: ArchiveWriter>(
+    mut archive: PackageArchive,
+    writer: &mut W,
+    options: &ExtractOptions,
+    previews: bool,
+) -> Result<ExtractReport> {
+    let mut converter = Converter {
+        options,
+        previews,
+        resolver: OutputResolver::new(Path::new(ARCHIVE_ROOT)),
+        writer,
+        report: ExtractReport::default(),
+    };
+
+    let entries = visit_package(&mut archive, options, &mut converter, || {})?;
+    let mut report = converter.report;
+    report.entries = entries;
+    Ok(report)
+}
@@ -0,0 +1,190 @@
+use anyhow::{Context, Result};
+use regex::bytes::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read};
+
+use crate::package::{PackageArchive, read_pathname, split_entry_path};
+
+/// GUIDs of Unity's built-in resources, which are never part of a package
+const BUILTIN_GUIDS: [&str; 4] = [
+    "00000000000000000000000000000000",
+    "0000000000000000d000000000000000",
+    "0000000000000000e000000000000000",
+    "0000000000000000f000000000000000",
+];
+
+/// GUID references between the assets of a package
+///
+/// Built by scanning text-serialized assets (prefabs, scenes, materials, ...) and every
+/// `asset.meta` for `{fileID: ..., guid: ..., type: ...}` references.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    /// Pathname of every GUID in the package
+    pub pathnames: BTreeMap<String, String>,
+    /// GUIDs referenced by each asset or its meta, for assets with at least one reference
+    pub references: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Whether `guid` belongs to Unity's built-in resources
+pub fn is_builtin_guid(guid: &str) -> bool {
+    BUILTIN_GUIDS.contains(&guid)
+}
+
+impl DependencyGraph {
+    pub fn pathname(&self, guid: &str) -> Option<&str> {
+        self.pathnames.get(guid).map(String::as_str)
+    }
+
+    /// GUIDs directly referenced by `guid`
+    pub fn dependencies(&self, guid: &str) -> impl Iterator<Item = &str> {
+        self.references.get(guid).into_iter().flatten().map(String::as_str)
+    }
+
+    /// GUID of the entry stored at `pathname`
+    pub fn guid_of(&self, pathname: &str) -> Option<&str> {
+        let pathname = pathname.trim_end_matches('/');
+        self.pathnames
+            .iter()
+            .find(|(_, p)| p.as_str() == pathname)
+            .map(|(guid, _)| guid.as_str())
+    }
+
+    /// `roots` plus every GUID of the package they reference, directly or transitively,
+    /// and the folder entries containing them
+    pub fn closure<'a>(&self, roots: impl IntoIterator<Item = &'a str>) -> HashSet<String> {
+        let mut closure = HashSet::new();
+        let mut queue: Vec<&str> = roots.into_iter().collect();
+
+        while let Some(guid) = queue.pop() {
+            if !self.pathnames.contains_key(guid) || !closure.insert(guid.to_string()) {
+                continue;
+            }
+            queue.extend(self.dependencies(guid));
+        }
+
+        // Folders leading to the selected assets keep their GUIDs too
+        let by_pathname: HashMap<&str, &str> = self
+            .pathnames
+            .iter()
+            .map(|(guid, pathname)| (pathname.as_str(), guid.as_str()))
+            .collect();
+        let folders: Vec<String> = closure
+            .iter()
+            .filter_map(|guid| self.pathname(guid))
+            .flat_map(|pathname| pathname.match_indices('/').map(|(i, _)| &pathname[..i]))
+            .filter_map(|folder| by_pathname.get(folder))
+            .map(|guid| guid.to_string())
+            .collect();
+        closure.extend(folders);
+        closure
+    }
+
+    /// References to GUIDs that are neither in the package nor built into Unity, grouped by referencing asset
+    pub fn unresolved(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        self.references
+            .iter()
+            .filter_map(|(guid, deps)| {
+                let missing: BTreeSet<&str> = deps
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|dep| !self.pathnames.contains_key(*dep) && !is_builtin_guid(dep))
+                    .collect();
+                (!missing.is_empty()).then_some((guid.as_str(), missing))
+            })
+            .collect()
+    }
+
+    /// Renders the graph in Graphviz DOT format; GUIDs missing from the package are dashed nodes
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n    rankdir=LR;\n    node [shape=box];\n");
+
+        let label = |guid: &str| self.pathname(guid).unwrap_or(guid).replace('\\', "\\\\").replace('"', "\\\"");
+
+        for (guid, deps) in &self.references {
+            for dep in deps {
+                if self.pathname(dep).is_none() {
+                    dot.push_str(&format!("    \"{}\" [style=dashed];\n", label(dep)));
+                }
+                dot.push_str(&format!("    \"{}\" -> \"{}\";\n", label(guid), label(dep)));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Whether the first line of an asset marks it as text-serialized Unity YAML
+fn is_yaml_header(line: &[u8]) -> bool {
+    line.starts_with(b"%YAML") || line.starts_with(b"--- !u!")
+}
+
+/// Collects every GUID referenced in a text-serialized asset, or `None` if it is binary.
+/// Metas have no YAML header, so `require_header` is disabled for them.
+pub(crate) fn scan_references(
+    reader: impl Read,
+    guid_pattern: &Regex,
+    require_header: bool,
+) -> Result<Option<BTreeSet<String>>> {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+
+    reader.read_until(b'\n', &mut line)?;
+    if require_header && !is_yaml_header(&line) {
+        return Ok(None);
+    }
+
+    let mut guids = BTreeSet::new();
+    loop {
+        for captures in guid_pattern.captures_iter(&line) {
+            guids.insert(String::from_utf8_lossy(&captures[1]).into_owned());
+        }
+
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+    }
+    Ok(Some(guids))
+}
+
+/// Matches `guid: <32 hex digits>` inside a YAML reference
+pub(crate) fn guid_reference_pattern() -> Regex {
+    Regex::new(r"guid:\s*([0-9a-f]{32})").expect("Invalid Regex")
+}
+
+/// Streams the archive once, scanning text-serialized assets for GUID references
+pub(crate) fn build_graph(mut archive: PackageArchive) -> Result<DependencyGraph> {
+    let guid_pattern = guid_reference_pattern();
+    let mut graph = DependencyGraph::default();
+
+    for entry in archive.entries().context("Error reading package contents")? {
+        let mut entry = entry.context("Error reading package contents")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let Some((guid, name)) = split_entry_path(&entry.path()?) else {
+            continue;
+        };
+
+        match name.as_str() {
+            "pathname" => {
+                graph.pathnames.insert(guid, read_pathname(&mut entry)?);
+            }
+            "asset" | "asset.meta" => {
+                let require_header = name == "asset";
+                if let Some(mut refs) = scan_references(&mut entry, &guid_pattern, require_header)? {
+                    // Sub-assets reference their own file, and metas declare their own GUID
+                    refs.remove(&guid);
+                    if !refs.is_empty() {
+                        graph.references.entry(guid).or_default().extend(refs);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(graph)
+}
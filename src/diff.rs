@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use similar::TextDiff;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Read};
+
+use crate::hash::copy_hashed;
+use crate::package::{PackageArchive, UnityPackage, is_folder_meta, read_pathname, split_entry_path};
+
+/// Content fingerprint of a single GUID directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryDigest {
+    pub pathname: String,
+    /// SHA-256 of the `asset` file, if any
+    pub asset_hash: Option<String>,
+    /// SHA-256 of the `asset.meta` file, if any
+    pub meta_hash: Option<String>,
+    pub size: u64,
+    /// Folder entry: no asset and `folderAsset: yes` in its meta
+    pub is_folder: bool,
+}
+
+/// How a GUID differs between two packages
+#[derive(Debug, Clone)]
+pub struct EntryChange {
+    pub guid: String,
+    pub old: Option<EntryDigest>,
+    pub new: Option<EntryDigest>,
+}
+
+impl EntryChange {
+    pub fn is_added(&self) -> bool {
+        self.old.is_none()
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.new.is_none()
+    }
+
+    /// Same GUID under a different pathname
+    pub fn is_moved(&self) -> bool {
+        matches!((&self.old, &self.new), (Some(old), Some(new)) if old.pathname != new.pathname)
+    }
+
+    pub fn asset_modified(&self) -> bool {
+        matches!((&self.old, &self.new), (Some(old), Some(new)) if old.asset_hash != new.asset_hash)
+    }
+
+    pub fn meta_modified(&self) -> bool {
+        matches!((&self.old, &self.new), (Some(old), Some(new)) if old.meta_hash != new.meta_hash)
+    }
+
+    /// Pathname in the newest package that has the entry
+    pub fn pathname(&self) -> &str {
+        self.new.as_ref().or(self.old.as_ref()).map(|d| d.pathname.as_str()).unwrap_or_default()
+    }
+}
+
+/// Differences between two packages, matched by GUID
+#[derive(Debug, Clone, Default)]
+pub struct PackageDiff {
+    /// Every GUID that was added, removed, moved or modified, sorted by pathname
+    pub changes: Vec<EntryChange>,
+}
+
+impl PackageDiff {
+    /// Compares two packages entry by entry
+    pub fn between(old: &UnityPackage, new: &UnityPackage) -> Result<Self> {
+        Ok(Self::compare(&old.digest()?, &new.digest()?))
+    }
+
+    pub fn compare(old: &BTreeMap<String, EntryDigest>, new: &BTreeMap<String, EntryDigest>) -> Self {
+        let guids: HashSet<&String> = old.keys().chain(new.keys()).collect();
+
+        let mut changes: Vec<EntryChange> = guids
+            .into_iter()
+            .filter(|guid| old.get(*guid) != new.get(*guid))
+            .map(|guid| EntryChange {
+                guid: guid.clone(),
+                old: old.get(guid).cloned(),
+                new: new.get(guid).cloned(),
+            })
+            .collect();
+        changes.sort_by(|a, b| a.pathname().cmp(b.pathname()).then_with(|| a.guid.cmp(&b.guid)));
+
+        Self { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Unified diff of two text assets, or `None` if either is binary
+pub fn unified_text_diff(old: &[u8], new: &[u8], old_name: &str, new_name: &str) -> Option<String> {
+    let is_text = |data: &[u8]| !data.contains(&0);
+    if !is_text(old) || !is_text(new) {
+        return None;
+    }
+    let old = std::str::from_utf8(old).ok()?;
+    let new = std::str::from_utf8(new).ok()?;
+
+    Some(
+        TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(3)
+            .header(&format!("a/{}", old_name), &format!("b/{}", new_name))
+            .to_string(),
+    )
+}
+
+/// Streams the archive once, hashing every asset and meta
+pub(crate) fn digest_archive(mut archive: PackageArchive) -> Result<BTreeMap<String, EntryDigest>> {
+    let mut digests: BTreeMap<String, EntryDigest> = BTreeMap::new();
+    let mut pathnames: HashSet<String> = HashSet::new();
+
+    for entry in archive.entries().context("Error reading package contents")? {
+        let mut entry = entry.context("Error reading package contents")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let Some((guid, name)) = split_entry_path(&entry.path()?) else {
+            continue;
+        };
+
+        match name.as_str() {
+            "pathname" => {
+                digests.entry(guid.clone()).or_default().pathname = read_pathname(&mut entry)?;
+                pathnames.insert(guid);
+            }
+            "asset" => {
+                let digest = digests.entry(guid).or_default();
+                digest.size = entry.header().size()?;
+                digest.asset_hash = Some(copy_hashed(&mut entry, &mut io::sink())?);
+            }
+            "asset.meta" => {
+                let mut meta = Vec::new();
+                entry.read_to_end(&mut meta)?;
+                let digest = digests.entry(guid).or_default();
+                digest.meta_hash = Some(copy_hashed(&mut meta.as_slice(), &mut io::sink())?);
+                digest.is_folder = is_folder_meta(&meta);
+            }
+            _ => {}
+        }
+    }
+
+    // Same rule as listing: entries without a pathname are not part of the package
+    digests.retain(|guid, _| pathnames.contains(guid));
+    for digest in digests.values_mut() {
+        digest.is_folder &= digest.asset_hash.is_none();
+    }
+    Ok(digests)
+}
+
+/// Text assets larger than this are not diffed, so they are not read either
+pub const MAX_TEXT_DIFF_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Streams the archive once, keeping the `asset` contents of the requested GUIDs that are text
+pub(crate) fn read_text_assets(mut archive: PackageArchive, guids: &HashSet<String>) -> Result<HashMap<String, Vec<u8>>> {
+    let mut assets = HashMap::new();
+
+    for entry in archive.entries().context("Error reading package contents")? {
+        let mut entry = entry.context("Error reading package contents")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let Some((guid, name)) = split_entry_path(&entry.path()?) else {
+            continue;
+        };
+
+        if name == "asset"
+            && guids.contains(&guid)
+            && entry.header().size()? <= MAX_TEXT_DIFF_BYTES
+            && let Some(data) = read_text(&mut entry)?
+        {
+            assets.insert(guid, data);
+        }
+    }
+
+    Ok(assets)
+}
+
+/// Reads a whole file, giving up at the first chunk with a NUL byte since it is then binary
+fn read_text(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(Some(data));
+        }
+        if chunk[..read].contains(&0) {
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk[..read]);
+    }
+}
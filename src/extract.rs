@@ -0,0 +1,549 @@
+use anyhow::{Context, Result, bail};
+use path_clean::PathClean;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tempfile::NamedTempFile;
+
+use crate::filter::PathFilter;
+use crate::hash::{copy_hashed, hash_file};
+use crate::package::{PackageArchive, PackageEntry};
+use crate::pool::WritePool;
+use crate::progress::{Progress, ProgressTracker};
+use crate::stream::{FileInfo, PackageVisitor, visit_package};
+use crate::transaction::Transaction;
+
+/// Options controlling what gets written during extraction
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    /// Write `<pathname>.meta` next to each asset so Unity keeps the original GUIDs
+    pub extract_meta: bool,
+    /// Only entries whose pathname passes this filter are written
+    pub filter: PathFilter,
+    /// When set, only entries with one of these GUIDs are written (see [`DependencyGraph::closure`])
+    ///
+    /// [`DependencyGraph::closure`]: crate::DependencyGraph::closure
+    pub guids: Option<HashSet<String>>,
+    /// What to do when a destination file already exists
+    pub conflict: ConflictPolicy,
+    /// Resolve paths and apply every policy decision without touching the disk
+    pub dry_run: bool,
+    /// Stage every file next to its destination and only move them into place once the whole
+    /// package has been read. If anything fails, overwritten files are restored and new files
+    /// and folders removed, so the destination is left as it was.
+    pub atomic: bool,
+    /// Number of threads writing files. Decisions and records stay in archive order;
+    /// `1` writes everything from the thread reading the archive. Ignored in atomic mode.
+    pub jobs: usize,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            extract_meta: true,
+            filter: PathFilter::default(),
+            guids: None,
+            conflict: ConflictPolicy::default(),
+            dry_run: false,
+            atomic: false,
+            jobs: 1,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Whether an asset passes both the GUID selection and the path filter
+    pub(crate) fn selects(&self, guid: &str, pathname: &str) -> bool {
+        self.selects_guid(guid) && self.filter.matches(pathname)
+    }
+
+    /// Whether a folder passes both the GUID selection and the path filter, given whether
+    /// a selected asset is inside it
+    pub(crate) fn selects_folder(&self, guid: &str, pathname: &str, holds_selected: bool) -> bool {
+        self.selects_guid(guid) && self.filter.matches_folder(pathname, holds_selected)
+    }
+
+    fn selects_guid(&self, guid: &str) -> bool {
+        self.guids.as_ref().is_none_or(|guids| guids.contains(guid))
+    }
+}
+
+/// What to do when a file being extracted already exists at its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Leave the existing file untouched
+    Skip,
+    /// Write the new file next to the existing one with a ` (n)` suffix
+    KeepBoth,
+    /// Replace the existing file only if its content hash differs
+    OverwriteIfDifferent,
+    /// Abort the extraction
+    Fail,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "keep-both" => Ok(ConflictPolicy::KeepBoth),
+            "overwrite-if-different" => Ok(ConflictPolicy::OverwriteIfDifferent),
+            "fail" => Ok(ConflictPolicy::Fail),
+            _ => bail!(
+                "Error: Unknown conflict policy '{}'. Expected overwrite, skip, keep-both, overwrite-if-different or fail.",
+                s
+            ),
+        }
+    }
+}
+
+/// Which part of a GUID directory a record refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    /// `<guid>/asset`, written to `<pathname>`
+    Asset,
+    /// `<guid>/asset.meta`, written to `<pathname>.meta`
+    Meta,
+    /// Folder entry (no `asset`, `folderAsset: yes` in its meta), created as directory `<pathname>`
+    Folder,
+}
+
+impl FileKind {
+    /// Stable snake_case name, used in machine-readable output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileKind::Asset => "asset",
+            FileKind::Meta => "meta",
+            FileKind::Folder => "folder",
+        }
+    }
+
+    /// Destination pathname for this file given the asset pathname
+    pub fn output_pathname(self, pathname: &str) -> String {
+        match self {
+            FileKind::Asset | FileKind::Folder => pathname.to_string(),
+            FileKind::Meta => format!("{}.meta", pathname),
+        }
+    }
+}
+
+/// What happened to a single file during extraction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractAction {
+    /// Written (or created, for folders) at a destination that did not exist yet
+    Extracted,
+    /// Replaced an existing file
+    Overwritten,
+    /// An identical file already existed ([`ConflictPolicy::OverwriteIfDifferent`])
+    Unchanged,
+    /// An existing file was left untouched ([`ConflictPolicy::Skip`])
+    SkippedExisting,
+    /// Written under a suffixed name next to an existing file ([`ConflictPolicy::KeepBoth`])
+    KeptBoth,
+    /// Not selected by the include/exclude filters or the GUID selection
+    Filtered,
+    /// The pathname resolves outside the destination directory (path traversal)
+    SkippedOutsideDestination,
+}
+
+impl ExtractAction {
+    /// Stable snake_case name, used in machine-readable output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExtractAction::Extracted => "extracted",
+            ExtractAction::Overwritten => "overwritten",
+            ExtractAction::Unchanged => "unchanged",
+            ExtractAction::SkippedExisting => "skipped_existing",
+            ExtractAction::KeptBoth => "kept_both",
+            ExtractAction::Filtered => "filtered",
+            ExtractAction::SkippedOutsideDestination => "skipped_outside_destination",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExtractRecord {
+    pub guid: String,
+    pub kind: FileKind,
+    /// Output pathname after sanitization (ends in `.meta` for [`FileKind::Meta`])
+    pub pathname: String,
+    /// Output pathname before the Windows sanitizer rewrote it, if it did
+    pub renamed_from: Option<String>,
+    pub destination: PathBuf,
+    pub action: ExtractAction,
+    /// SHA-256 of the file contents from the package, when they were written or compared
+    pub hash: Option<String>,
+}
+
+/// Everything that was done (or would be done, for a dry run) during an extraction
+#[derive(Debug, Clone, Default)]
+pub struct ExtractReport {
+    /// One record per file or folder handled, in archive order
+    pub records: Vec<ExtractRecord>,
+    /// Every entry with a `pathname` seen in the package, sorted by pathname
+    pub entries: Vec<PackageEntry>,
+}
+
+impl ExtractReport {
+    /// Number of records that ended with `action`
+    pub fn count(&self, action: ExtractAction) -> usize {
+        self.records.iter().filter(|r| r.action == action).count()
+    }
+
+    /// Records of a single entry
+    pub fn records_for<'a>(&'a self, guid: &'a str) -> impl Iterator<Item = &'a ExtractRecord> + 'a {
+        self.records.iter().filter(move |r| r.guid == guid)
+    }
+}
+
+/// Resolves and validates the final location of an asset inside the output directory
+pub(crate) struct OutputResolver {
+    output_path: PathBuf,
+    output_path_abs: PathBuf,
+    windows_bad_chars: Regex,
+}
+
+impl OutputResolver {
+    pub(crate) fn new(output_path: &Path) -> Self {
+        // Resolve absolute path for security checks
+        let output_path_abs = output_path.canonicalize().unwrap_or(output_path.to_path_buf());
+
+        // Regex compiled once
+        // > : " | ? * are forbidden characters in Windows filenames
+        let windows_bad_chars = Regex::new(r#"[>:"|?*]"#).expect("Invalid Regex");
+
+        Self {
+            output_path: output_path.to_path_buf(),
+            output_path_abs,
+            windows_bad_chars,
+        }
+    }
+
+    pub(crate) fn resolve(&self, pathname: &str) -> Resolved {
+        let mut sanitized = pathname.to_string();
+
+        // Sanitization for Windows
+        if cfg!(windows) {
+            sanitized = self.windows_bad_chars.replace_all(&sanitized, "_").to_string();
+        }
+
+        // Construct final path
+        let destination = self.output_path.join(&sanitized);
+
+        // Security Check: Prevent Path Traversal (Zip Slip vulnerability logic)
+        let resolved_out_path = self.output_path_abs.join(&sanitized).clean();
+        let inside = resolved_out_path.starts_with(&self.output_path_abs);
+
+        let renamed_from = (sanitized != pathname).then(|| pathname.to_string());
+        Resolved {
+            pathname: sanitized,
+            renamed_from,
+            destination,
+            inside,
+        }
+    }
+}
+
+/// Final location of a pathname inside the output directory
+pub(crate) struct Resolved {
+    /// Pathname after sanitization
+    pub(crate) pathname: String,
+    /// Original pathname when the sanitizer changed it
+    pub(crate) renamed_from: Option<String>,
+    pub(crate) destination: PathBuf,
+    /// Whether the destination stays inside the output directory
+    pub(crate) inside: bool,
+}
+
+struct Extractor<'a, F> {
+    options: &'a ExtractOptions,
+    resolver: OutputResolver,
+    /// Set in atomic mode
+    transaction: Option<Transaction>,
+    /// Set when writing with several jobs
+    pool: Option<WritePool>,
+    /// Folders that did not exist yet when the extraction first needed them
+    new_dirs: HashSet<PathBuf>,
+    report: ExtractReport,
+    on_record: F,
+}
+
+impl<F: FnMut(&ExtractRecord)> Extractor<'_, F> {
+    fn write_file(&mut self, guid: &str, kind: FileKind, pathname: &str, selected: bool, mut asset: impl Read) -> Result<()> {
+        let mut resolved = self.resolver.resolve(&kind.output_pathname(pathname));
+        let dry_run = self.options.dry_run;
+
+        let mut hash = None;
+        let action = if !selected {
+            ExtractAction::Filtered
+        } else if !resolved.inside {
+            ExtractAction::SkippedOutsideDestination
+        } else {
+            let destination = &resolved.destination;
+            if let Some(parent) = destination.parent() {
+                self.note_new_dirs(parent);
+            }
+            // Workers create the folders of the files they write
+            if !dry_run && self.pool.is_none() && let Some(parent) = destination.parent() {
+                self.create_dir_all(parent)?;
+            }
+
+            if !self.is_occupied(destination) {
+                hash = Some(self.write(destination, &mut asset)?);
+                ExtractAction::Extracted
+            } else {
+                match self.options.conflict {
+                    ConflictPolicy::Overwrite => {
+                        hash = Some(self.write(destination, &mut asset)?);
+                        ExtractAction::Overwritten
+                    }
+                    ConflictPolicy::Skip => ExtractAction::SkippedExisting,
+                    ConflictPolicy::Fail => {
+                        bail!("Error: '{}' already exists.", destination.display());
+                    }
+                    ConflictPolicy::KeepBoth => {
+                        let free = self.free_pathname(kind, &resolved.pathname);
+                        resolved.pathname = free.pathname;
+                        resolved.destination = free.destination;
+                        hash = Some(self.write(&resolved.destination, &mut asset)?);
+                        ExtractAction::KeptBoth
+                    }
+                    ConflictPolicy::OverwriteIfDifferent => {
+                        let (replaced, incoming) = match (&mut self.transaction, &mut self.pool) {
+                            (Some(transaction), _) => transaction.stage_if_different(destination, &mut asset)?,
+                            (None, Some(pool)) => {
+                                pool.settle(destination)?;
+                                replace_if_different(destination, &mut asset, dry_run)?
+                            }
+                            (None, None) => replace_if_different(destination, &mut asset, dry_run)?,
+                        };
+                        hash = Some(incoming);
+                        if replaced {
+                            ExtractAction::Overwritten
+                        } else {
+                            ExtractAction::Unchanged
+                        }
+                    }
+                }
+            }
+        };
+
+        self.record(guid, kind, resolved, action, hash);
+        Ok(())
+    }
+
+    /// First `<name> (n).<ext>` variant of `pathname` that does not exist yet
+    fn free_pathname(&self, kind: FileKind, pathname: &str) -> Resolved {
+        // Metas keep their `.meta` extension after the suffixed asset name
+        let base = match kind {
+            FileKind::Meta => pathname.strip_suffix(".meta").unwrap_or(pathname),
+            _ => pathname,
+        };
+        let (dir, file) = match base.rsplit_once('/') {
+            Some((dir, file)) => (format!("{}/", dir), file),
+            None => (String::new(), base),
+        };
+        let (stem, ext) = match file.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+            _ => (file, String::new()),
+        };
+
+        (1..)
+            .map(|n| self.resolver.resolve(&kind.output_pathname(&format!("{}{} ({}){}", dir, stem, n, ext))))
+            .find(|candidate| !self.is_occupied(&candidate.destination))
+            .expect("Unbounded suffix search")
+    }
+
+    /// Writes (or stages, in atomic mode) a whole file, returning its hash
+    fn write(&mut self, destination: &Path, asset: &mut impl Read) -> Result<String> {
+        match (&mut self.transaction, &mut self.pool) {
+            (Some(transaction), _) => transaction.stage(destination, asset),
+            (None, Some(pool)) => pool.write(destination, asset),
+            (None, None) => write_new(destination, asset, self.options.dry_run),
+        }
+    }
+
+    fn create_dir_all(&mut self, path: &Path) -> Result<()> {
+        match &mut self.transaction {
+            Some(transaction) => transaction.create_dir_all(path),
+            None => fs::create_dir_all(path).with_context(|| format!("Could not create folder '{}'", path.display())),
+        }
+    }
+
+    /// Whether a file exists at `destination`, or is staged or queued to be written there
+    fn is_occupied(&self, destination: &Path) -> bool {
+        destination.exists()
+            || self.transaction.as_ref().is_some_and(|t| t.is_staged(destination))
+            || self.pool.as_ref().is_some_and(|p| p.is_pending(destination))
+    }
+
+    /// Remembers which of `dir` and its ancestors do not exist yet, before anything creates them
+    fn note_new_dirs(&mut self, dir: &Path) {
+        for ancestor in dir.ancestors() {
+            if self.new_dirs.contains(ancestor) || ancestor.as_os_str().is_empty() || ancestor.is_dir() {
+                break;
+            }
+            self.new_dirs.insert(ancestor.to_path_buf());
+        }
+    }
+
+    fn create_folder(&mut self, guid: &str, pathname: &str) -> Result<()> {
+        let resolved = self.resolver.resolve(pathname);
+        if resolved.inside {
+            self.note_new_dirs(&resolved.destination);
+        }
+
+        // A folder that was there before the extraction is left as it is
+        let action = if !resolved.inside {
+            ExtractAction::SkippedOutsideDestination
+        } else if !self.new_dirs.contains(&resolved.destination) {
+            ExtractAction::Unchanged
+        } else {
+            if !self.options.dry_run {
+                self.create_dir_all(&resolved.destination)?;
+            }
+            ExtractAction::Extracted
+        };
+
+        self.record(guid, FileKind::Folder, resolved, action, None);
+        Ok(())
+    }
+
+    /// Handles a GUID's meta once its pathname is known: creates folders and writes the `.meta`
+    fn finish_meta(&mut self, guid: &str, pathname: &str, selected: bool, is_folder: bool, meta: &[u8]) -> Result<()> {
+        if is_folder {
+            if selected {
+                self.create_folder(guid, pathname)?;
+            } else {
+                let resolved = self.resolver.resolve(pathname);
+                self.record(guid, FileKind::Folder, resolved, ExtractAction::Filtered, None);
+            }
+        }
+        if !self.options.extract_meta {
+            return Ok(());
+        }
+
+        // Folders are merged rather than duplicated, so their meta stays next to the existing one
+        let resolved = self.resolver.resolve(&FileKind::Meta.output_pathname(pathname));
+        let keep_existing = is_folder && self.options.conflict == ConflictPolicy::KeepBoth;
+        if keep_existing && selected && resolved.inside && self.is_occupied(&resolved.destination) {
+            self.record(guid, FileKind::Meta, resolved, ExtractAction::SkippedExisting, None);
+            return Ok(());
+        }
+        self.write_file(guid, FileKind::Meta, pathname, selected, meta)
+    }
+
+    fn record(&mut self, guid: &str, kind: FileKind, resolved: Resolved, action: ExtractAction, hash: Option<String>) {
+        let record = ExtractRecord {
+            guid: guid.to_string(),
+            kind,
+            pathname: resolved.pathname,
+            renamed_from: resolved.renamed_from,
+            destination: resolved.destination,
+            action,
+            hash,
+        };
+        (self.on_record)(&record);
+        self.report.records.push(record);
+    }
+}
+
+impl<F: FnMut(&ExtractRecord)> PackageVisitor for Extractor<'_, F> {
+    fn asset(&mut self, guid: &str, pathname: &str, selected: bool, _info: FileInfo, data: &mut dyn Read) -> Result<()> {
+        self.write_file(guid, FileKind::Asset, pathname, selected, data)
+    }
+
+    fn meta(&mut self, guid: &str, pathname: &str, selected: bool, is_folder: bool, _info: FileInfo, meta: &[u8]) -> Result<()> {
+        self.finish_meta(guid, pathname, selected, is_folder, meta)
+    }
+}
+
+/// Creates (or truncates) `destination` with the contents of `asset`, returning their hash
+pub(crate) fn write_new(destination: &Path, asset: &mut impl Read, dry_run: bool) -> Result<String> {
+    if dry_run {
+        return Ok(copy_hashed(asset, &mut io::sink())?);
+    }
+
+    let mut out = File::create(destination)
+        .with_context(|| format!("Could not create '{}'", destination.display()))?;
+    Ok(copy_hashed(asset, &mut out)?)
+}
+
+/// Replaces `destination` with `asset` unless both have the same content hash.
+/// Returns whether the file was (or, for a dry run, would be) replaced, and the hash of `asset`.
+fn replace_if_different(destination: &Path, asset: &mut impl Read, dry_run: bool) -> Result<(bool, String)> {
+    if dry_run {
+        let incoming = copy_hashed(asset, &mut io::sink())?;
+        let replaced = incoming != hash_file(destination)?;
+        return Ok((replaced, incoming));
+    }
+
+    let parent = destination.parent().unwrap_or(Path::new("."));
+    let mut staged = NamedTempFile::new_in(parent)
+        .with_context(|| format!("Could not create a temporary file in '{}'", parent.display()))?;
+
+    let incoming = copy_hashed(asset, &mut staged)?;
+    if incoming == hash_file(destination)? {
+        return Ok((false, incoming));
+    }
+
+    staged
+        .persist(destination)
+        .with_context(|| format!("Could not replace '{}'", destination.display()))?;
+    Ok((true, incoming))
+}
+
+/// Streams the archive once, writing assets straight to their final location
+pub(crate) fn extract_archive(
+    mut archive: PackageArchive,
+    output_path: &Path,
+    options: &ExtractOptions,
+    on_record: impl FnMut(&ExtractRecord),
+    mut progress: ProgressTracker<impl FnMut(&Progress)>,
+) -> Result<ExtractReport> {
+    let mut extractor = Extractor {
+        options,
+        resolver: OutputResolver::new(output_path),
+        transaction: (options.atomic && !options.dry_run).then(Transaction::default),
+        pool: (options.jobs > 1 && !options.atomic && !options.dry_run).then(|| WritePool::new(options.jobs)),
+        new_dirs: HashSet::new(),
+        report: ExtractReport::default(),
+        on_record,
+    };
+
+    // Single pass over the archive: files are written as soon as their pathname is known
+    let mut result = visit_package(&mut archive, options, &mut extractor, || progress.entry());
+    progress.report();
+    if let Some(pool) = extractor.pool.take() {
+        // Always wait for the workers, even when reading failed
+        let finished = pool.finish();
+        result = result.and_then(|entries| finished.map(|()| entries));
+    }
+
+    let entries = match (result, extractor.transaction.take()) {
+        (Ok(entries), Some(transaction)) => {
+            transaction.commit()?;
+            entries
+        }
+        (Ok(entries), None) => entries,
+        (Err(e), transaction) => {
+            if let Some(transaction) = transaction {
+                transaction.rollback();
+            }
+            return Err(e);
+        }
+    };
+
+    let mut report = extractor.report;
+    report.entries = entries;
+    Ok(report)
+}
+
@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+/// A glob pattern matched against asset pathnames
+///
+/// * `**` matches any sequence of characters, including `/`
+/// * `*` matches any sequence of characters except `/`
+/// * `?` matches a single character except `/`
+/// * `[...]` matches a character class
+#[derive(Debug, Clone)]
+pub struct Glob {
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(&glob_to_regex(pattern))
+            .with_context(|| format!("Error: Invalid glob pattern '{}'.", pattern))?;
+        Ok(Self { regex })
+    }
+
+    pub fn is_match(&self, pathname: &str) -> bool {
+        self.regex.is_match(pathname)
+    }
+}
+
+/// Include/exclude glob filters evaluated against the `pathname` of each entry
+///
+/// An entry is selected when it matches at least one include pattern (or there
+/// are none) and no exclude pattern.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    includes: Vec<Glob>,
+    excludes: Vec<Glob>,
+}
+
+impl PathFilter {
+    pub fn new<I, E>(includes: I, excludes: E) -> Result<Self>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+        E: IntoIterator,
+        E::Item: AsRef<str>,
+    {
+        Ok(Self {
+            includes: includes.into_iter().map(|p| Glob::new(p.as_ref())).collect::<Result<_>>()?,
+            excludes: excludes.into_iter().map(|p| Glob::new(p.as_ref())).collect::<Result<_>>()?,
+        })
+    }
+
+    /// Whether an asset (file) pathname is selected
+    pub fn matches(&self, pathname: &str) -> bool {
+        let included = self.includes.is_empty() || self.includes.iter().any(|g| g.is_match(pathname));
+        included && !self.excludes.iter().any(|g| g.is_match(pathname))
+    }
+
+    /// Whether a folder pathname is selected, given whether it holds selected assets
+    ///
+    /// Folders are kept when they match a pattern themselves or hold selected assets, so
+    /// `Assets/*/Scripts/*.cs` also recreates `Assets/Vendor` and `Assets/Vendor/Scripts`
+    /// when there is a script in them, but no other folder.
+    pub fn matches_folder(&self, pathname: &str, holds_selected: bool) -> bool {
+        let inside = format!("{}/", pathname);
+        let matches = |g: &Glob| g.is_match(pathname) || g.is_match(&inside);
+        let included = self.includes.is_empty() || holds_selected || self.includes.iter().any(matches);
+        included && !self.excludes.iter().any(matches)
+    }
+}
+
+/// Translates a glob pattern into an anchored regular expression
+fn glob_to_regex(pattern: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches zero directories
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                regex.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    regex.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+            }
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(includes: &[&str], excludes: &[&str]) -> PathFilter {
+        PathFilter::new(includes, excludes).unwrap()
+    }
+
+    #[test]
+    fn glob_wildcards() {
+        let glob = Glob::new("Assets/*/Scripts/*.cs").unwrap();
+        assert!(glob.is_match("Assets/Vendor/Scripts/Player.cs"));
+        assert!(!glob.is_match("Assets/Vendor/Scripts/Editor/Tool.cs"));
+        assert!(!glob.is_match("Assets/Scripts/Player.cs"));
+
+        let glob = Glob::new("**/*.cs").unwrap();
+        assert!(glob.is_match("Player.cs"));
+        assert!(glob.is_match("Assets/Vendor/Scripts/Player.cs"));
+        assert!(!glob.is_match("Assets/Player.cs.meta"));
+
+        let glob = Glob::new("Assets/Te?t[0-9].[!c]*").unwrap();
+        assert!(glob.is_match("Assets/Test1.png"));
+        assert!(!glob.is_match("Assets/Test1.cs"));
+        assert!(!glob.is_match("Assets/Te/t1.png"));
+
+        // Regex characters are literal
+        assert!(Glob::new("Assets/(a+b).txt").unwrap().is_match("Assets/(a+b).txt"));
+        assert!(!Glob::new("Assets/a.txt").unwrap().is_match("Assets/abtxt"));
+    }
+
+    #[test]
+    fn folders_are_selected_by_their_contents() {
+        let filter = filter(&["Assets/*/Scripts/*.cs"], &[]);
+        // Sibling folders that no selected asset is in are left out
+        assert!(!filter.matches_folder("Assets/Empty", false));
+        assert!(!filter.matches_folder("Assets/Vendor/Textures", false));
+        assert!(filter.matches_folder("Assets/Vendor", true));
+        assert!(filter.matches_folder("Assets/Vendor/Scripts", true));
+    }
+
+    #[test]
+    fn folders_matching_a_pattern_themselves() {
+        let filter = filter(&["Assets/Vendor/Scripts/**", "Assets/Empty"], &[]);
+        assert!(filter.matches_folder("Assets/Vendor/Scripts", false));
+        assert!(filter.matches_folder("Assets/Empty", false));
+        assert!(!filter.matches_folder("Assets/Vendor", false));
+        assert!(!filter.matches_folder("Assets/Vendor/Textures", false));
+    }
+
+    #[test]
+    fn excluded_folders() {
+        let filter = filter(&[], &["Assets/Vendor/Textures/**"]);
+        assert!(filter.matches_folder("Assets/Vendor", false));
+        assert!(filter.matches_folder("Assets/Vendor/Texts", false));
+        assert!(!filter.matches_folder("Assets/Vendor/Textures", true));
+        assert!(!filter.matches("Assets/Vendor/Textures/T.png"));
+        assert!(filter.matches("Assets/Vendor/Scripts/A.cs"));
+    }
+}
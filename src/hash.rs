@@ -0,0 +1,27 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+/// Hex-encoded SHA-256 of a file's contents
+pub fn hash_file(path: &Path) -> Result<String> {
+    let file = File::open(path).with_context(|| format!("Could not open '{}'", path.display()))?;
+    let mut reader = BufReader::new(file);
+    Ok(copy_hashed(&mut reader, &mut io::sink())?)
+}
+
+/// Copies `reader` into `writer`, returning the hex-encoded SHA-256 of the copied bytes
+pub fn copy_hashed(reader: &mut impl Read, writer: &mut impl Write) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,43 @@
+//! Read and extract `.unitypackage` files without the Unity Editor.
+//!
+//! A `.unitypackage` is a gzip-compressed tar archive with one directory per asset,
+//! named after the asset GUID:
+//!
+//! ```text
+//! <guid>/pathname     Real path of the asset inside the project (e.g. Assets/Scripts/Player.cs)
+//! <guid>/asset        File contents (absent for folders)
+//! <guid>/asset.meta   Unity metadata, including the GUID
+//! <guid>/preview.png  Optional thumbnail
+//! ```
+
+mod cat;
+mod convert;
+pub mod deps;
+pub mod diff;
+pub mod extract;
+pub mod filter;
+pub mod hash;
+pub mod manifest;
+pub mod pack;
+pub mod package;
+mod pool;
+pub mod preview;
+pub mod progress;
+mod stream;
+mod transaction;
+pub mod uninstall;
+pub mod upgrade;
+pub mod verify;
+
+pub use deps::DependencyGraph;
+pub use diff::PackageDiff;
+pub use extract::{ConflictPolicy, ExtractAction, ExtractOptions, ExtractRecord, ExtractReport, FileKind};
+pub use filter::PathFilter;
+pub use manifest::{InstalledFile, Manifest, RECEIPT_FOLDER};
+pub use pack::{PackReport, pack_directory};
+pub use package::{PackageEntry, UnityPackage};
+pub use preview::PreviewRecord;
+pub use progress::Progress;
+pub use uninstall::{UninstallAction, UninstallOptions, UninstallReport, uninstall};
+pub use upgrade::{UpgradeAction, UpgradeConflict, UpgradeOptions, UpgradeRecord, UpgradeReport};
+pub use verify::{IssueCategory, VerifyReport};
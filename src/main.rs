@@ -1,121 +1,113 @@
-use anyhow::{Context, Result, bail};
-use flate2::read::GzDecoder;
-use regex::Regex;
+mod commands;
+
+use anyhow::{Result, bail};
 use std::env;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
-use std::path::Path;
-use std::time::Instant;
-use tempfile::TempDir;
-use path_clean::PathClean;
 
 /// Displays help and correct program usage
 fn print_help(program_name: &str) {
     println!("UnityPackage Extractor (Rust Version)");
     println!("---------------------------------------");
-    println!("Usage: {} <file.unitypackage> [output_path]", program_name);
+    println!("Usage: {} <file.unitypackage> [output_path] [options]", program_name);
+    println!("       {} <file.unitypackage|-> --to-tar [options] > assets.tar", program_name);
+    println!("       {} <file.unitypackage|-> --to-zip <output.zip> [--previews] [options]", program_name);
+    println!("       {} list <file.unitypackage> [--tree|--flat] [--format <format>]", program_name);
+    println!("       {} deps <file.unitypackage> [--missing] [--format <text|json|dot>]", program_name);
+    println!("       {} verify <file.unitypackage> [--format <format>]", program_name);
+    println!("       {} diff <old.unitypackage> <new.unitypackage> [--unified] [--format <format>]", program_name);
+    println!("       {} gallery <file.unitypackage> <output_dir> [--include <glob>]...", program_name);
+    println!("       {} upgrade <new.unitypackage> [output_path] --manifest <file> [options]", program_name);
+    println!("       {} uninstall <file.unitypackage|receipt.json> [output_path] [--force]", program_name);
+    println!("       {} cat <file.unitypackage|-> <pathname|guid> [--meta]", program_name);
+    println!("       {} batch <file.unitypackage|folder>... [--output <dir>] [--shared] [options]", program_name);
+    println!("       {} pack <folder> <output.unitypackage>", program_name);
+    println!();
+    println!("Commands:");
+    println!("  list                    Print the package contents without extracting.");
+    println!("                          Shows GUID, asset size and A/M/P flags for");
+    println!("                          asset, meta and preview presence.");
+    println!("  deps                    Print the GUID dependency graph of the text-serialized");
+    println!("                          assets (prefabs, scenes, materials...) as text,");
+    println!("                          JSON or Graphviz DOT. With --missing, list references");
+    println!("                          to GUIDs not included in the package and exit with 1.");
+    println!("  verify                  Check the package integrity (gzip CRC, truncation) and");
+    println!("                          layout (missing pathname/asset/meta, malformed or");
+    println!("                          mismatched GUIDs, duplicate pathnames). Exits with 1");
+    println!("                          if any issue is found.");
+    println!("  diff                    Compare two packages by GUID: added (A), removed (D),");
+    println!("                          moved (R), asset modified (M) and meta modified (m).");
+    println!("                          --unified also prints text diffs of modified assets.");
+    println!("  gallery                 Extract the preview thumbnails as <pathname>.png into");
+    println!("                          <output_dir> with an index.html showing each asset's");
+    println!("                          thumbnail, pathname, size and type.");
+    println!("  upgrade                 Apply a new version over a previous extraction recorded");
+    println!("                          with --manifest: delete files removed upstream, move");
+    println!("                          files whose GUID moved and update files not modified");
+    println!("                          locally. Files changed on both sides are left as they");
+    println!("                          are and reported as conflicts (exit status 1).");
+    println!("  uninstall               Remove the files and emptied folders recorded in the");
+    println!("                          receipt written by the extraction. Files modified");
+    println!("                          since then are kept (exit status 1) unless --force.");
+    println!("  cat                     Print one asset (by pathname or GUID) to standard");
+    println!("                          output, stopping as soon as it has been read.");
+    println!("  batch                   Extract several packages, and every .unitypackage found");
+    println!("                          in the given folders, each into <output>/<name>/.");
+    println!("                          Failures do not stop the batch; a summary is printed");
+    println!("                          and the exit status is 1 if any package failed.");
+    println!("  pack                    Create a .unitypackage from a folder of assets.");
+    println!("                          Every file and folder needs its .meta (for the GUID).");
     println!();
     println!("Arguments:");
-    println!("  <file.unitypackage>     Path to the file you want to extract.");
+    println!("  <file.unitypackage>     Path to the file you want to extract, or '-' to read");
+    println!("                          it from standard input.");
     println!("  [output_path]           (Optional) Folder where to extract files.");
     println!("                          Defaults to the current directory.");
     println!();
     println!("Options:");
+    println!("  --no-meta               Do not write the .meta files. Without them Unity");
+    println!("                          assigns new GUIDs and references between assets break.");
+    println!("  --include <glob>        Only extract entries whose pathname matches. Repeatable.");
+    println!("  --exclude <glob>        Skip entries whose pathname matches. Repeatable.");
+    println!("                          '*' matches within a folder, '**' across folders,");
+    println!("                          e.g. --include 'Assets/Vendor/Scripts/**'.");
+    println!("  --with-deps <asset>     Only extract the asset (pathname or GUID) and everything");
+    println!("                          it references within the package, transitively.");
+    println!("                          Repeatable.");
+    println!("  --on-conflict <policy>  What to do when a file already exists:");
+    println!("                          overwrite (default), skip, keep-both,");
+    println!("                          overwrite-if-different or fail.");
+    println!("  --dry-run               Show what would be created, overwritten, skipped or");
+    println!("                          renamed without writing anything.");
+    println!("  --atomic                Stage every file and only move them into place once");
+    println!("                          the whole package was read. On failure, nothing is");
+    println!("                          left behind and overwritten files are restored.");
+    println!("  -j, --jobs <n>          Threads writing files (default: one per CPU core).");
+    println!("                          Output and conflict handling stay in package order.");
+    println!("  --progress              Show bytes read, entries, throughput and ETA instead");
+    println!("                          of one line per file (a plain line every 5 seconds");
+    println!("                          when the output is not a terminal).");
+    println!("  --format <format>       Output format: text (default), json or ndjson.");
+    println!("                          JSON prints one record per entry with its GUID,");
+    println!("                          pathname, size, meta/preview presence and action.");
+    println!("  --manifest <file>       Write the list of extracted files and their SHA-256 to");
+    println!("                          <file>, for a later upgrade. (upgrade) The manifest of");
+    println!("                          the previous extraction; it is updated in place.");
+    println!("  --no-receipt            Do not write the extraction receipt to");
+    println!("                          <output_path>/.unitypackage-receipts/.");
+    println!("  --force                 (uninstall) Also remove files modified since extraction.");
+    println!("  --meta                  (cat) Print the .meta instead of the asset.");
+    println!("  --to-tar                Write the assets to standard output as a tar named by");
+    println!("                          pathname instead of extracting them.");
+    println!("  --to-zip <file>         Write the assets to a zip file named by pathname");
+    println!("                          instead of extracting them.");
+    println!("  --previews              (--to-zip) Also add thumbnails as <pathname>.png.");
+    println!("  -o, --output <dir>      (batch) Output folder (default: current directory).");
+    println!("  --shared                (batch) Extract every package into the same tree.");
+    println!("  --tree                  (list) Show contents as a directory tree.");
+    println!("  --flat                  (list) Show contents as a flat listing (the default),");
+    println!("                          overriding an earlier --tree.");
     println!("  -h, --help              Show this help message.");
 }
 
-fn extract_package(package_path: &Path, output_path: Option<&Path>) -> Result<()> {
-    // Determine output path (cwd by default)
-    let cwd = env::current_dir()?;
-    let output_path = output_path.unwrap_or(&cwd);
-    
-    // Resolve absolute path for security checks
-    let output_path_abs = output_path.canonicalize().unwrap_or(output_path.to_path_buf());
-
-    // Create temporary directory
-    let tmp_dir = TempDir::new()?;
-    let tmp_path = tmp_dir.path();
-
-    println!("Unpacking file temporarily...");
-
-    // Open .unitypackage (tar.gz)
-    let file = File::open(package_path).context("Could not open .unitypackage file")?;
-    let tar = GzDecoder::new(file);
-    let mut archive = tar::Archive::new(tar);
-
-    // Unpack everything to temp
-    archive.unpack(tmp_path).context("Error unpacking to temporary directory")?;
-
-    // Regex compiled once
-    // > : " | ? * are forbidden characters in Windows filenames
-    let windows_bad_chars = Regex::new(r#"[>:"|?*]"#).expect("Invalid Regex");
-
-    // Iterate through directories in temp
-    for entry in fs::read_dir(tmp_path)? {
-        let entry = entry?;
-        let entry_path = entry.path();
-
-        if !entry_path.is_dir() {
-            continue;
-        }
-
-        let pathname_file = entry_path.join("pathname");
-        let asset_file = entry_path.join("asset");
-
-        if !pathname_file.exists() || !asset_file.exists() {
-            continue;
-        }
-
-        // Read the 'pathname' file containing the real asset path
-        let file = File::open(&pathname_file)?;
-        let mut reader = BufReader::new(file);
-        let mut pathname = String::new();
-        reader.read_line(&mut pathname)?;
-        
-        let mut pathname = pathname.trim_end().to_string();
-
-        // Sanitization for Windows
-        if cfg!(windows) {
-            pathname = windows_bad_chars.replace_all(&pathname, "_").to_string();
-        }
-
-        // Construct final path
-        let asset_out_path = output_path.join(&pathname);
-        
-        // Security Check: Prevent Path Traversal (Zip Slip vulnerability logic)
-        let resolved_out_path = output_path_abs.join(&pathname).clean();
-        
-        if !resolved_out_path.starts_with(&output_path_abs) {
-            println!("WARNING: Skipping '{}' as '{}' is outside the destination path '{}'.", 
-                entry.file_name().to_string_lossy(), 
-                asset_out_path.display(), 
-                output_path.display()
-            );
-            continue;
-        }
-
-        println!("Extracting '{}' as '{}'", entry.file_name().to_string_lossy(), pathname);
-
-        if let Some(parent) = asset_out_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        move_file(&asset_file, &asset_out_path)?;
-    }
-
-    Ok(())
-}
-
-fn move_file(src: &Path, dst: &Path) -> Result<()> {
-    // Attempt rename (fast, but fails across different drives/partitions)
-    if fs::rename(src, dst).is_err() {
-        // Fallback: Copy and delete
-        fs::copy(src, dst)?;
-        fs::remove_file(src)?;
-    }
-    Ok(())
-}
-
 fn cli() -> Result<()> {
     let args: Vec<String> = env::args().collect();
     let program_name = args.first().map(|s| s.as_str()).unwrap_or("unitypackage_extractor");
@@ -133,25 +125,19 @@ fn cli() -> Result<()> {
         bail!("Error: You must specify at least the .unitypackage file.");
     }
 
-    let package_path = Path::new(&args[1]);
-
-    // 3. Check input file existence
-    if !package_path.exists() {
-        bail!("Error: The file '{}' does not exist.", package_path.display());
+    match args[1].as_str() {
+        "list" => commands::list::run(&args[2..]),
+        "batch" => commands::batch::run(&args[2..]),
+        "cat" => commands::cat::run(&args[2..]),
+        "deps" => commands::deps::run(&args[2..]),
+        "pack" => commands::pack::run(&args[2..]),
+        "verify" => commands::verify::run(&args[2..]),
+        "diff" => commands::diff::run(&args[2..]),
+        "upgrade" => commands::upgrade::run(&args[2..]),
+        "gallery" => commands::gallery::run(&args[2..]),
+        "uninstall" => commands::uninstall::run(&args[2..]),
+        _ => commands::extract::run(&args[1..]),
     }
-
-    let output_path = if args.len() > 2 {
-        Some(Path::new(&args[2]))
-    } else {
-        None
-    };
-
-    let start_time = Instant::now();
-    extract_package(package_path, output_path)?;
-    let duration = start_time.elapsed();
-
-    println!("--- Finished in {:.4} seconds ---", duration.as_secs_f64());
-    Ok(())
 }
 
 fn main() {
@@ -0,0 +1,263 @@
+use anyhow::{Context, Result, bail};
+use std::collections::HashSet;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::extract::{ExtractAction, ExtractReport, FileKind};
+
+/// Folder inside the output directory holding one receipt per extracted package.
+/// Unity ignores names starting with a dot, so it is never imported as an asset.
+pub const RECEIPT_FOLDER: &str = ".unitypackage-receipts";
+
+/// A file or folder that a package placed in the output directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledFile {
+    pub guid: String,
+    pub kind: FileKind,
+    /// Output pathname relative to the output directory (ends in `.meta` for [`FileKind::Meta`])
+    pub pathname: String,
+    /// SHA-256 of the contents as written by the package (`None` for folders)
+    pub hash: Option<String>,
+}
+
+/// Record of the files written by an extraction, so a later version can be applied on top
+/// or the package can be uninstalled. Also used as the receipt kept in [`RECEIPT_FOLDER`].
+///
+/// Stored as JSON: `{"package": ..., "files": [{"guid": ..., "kind": "asset", "pathname": ..., "sha256": ...}, ...]}`
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    /// Name of the package the files come from (see [`Manifest::package_name`]), so its
+    /// receipt can be found; unknown for packages read from standard input and older manifests
+    pub package: Option<String>,
+    pub files: Vec<InstalledFile>,
+}
+
+impl Manifest {
+    /// Files an extraction wrote or found identical, and the folders it created;
+    /// skipped and filtered files and folders that already existed are left out
+    pub fn from_report(report: &ExtractReport) -> Self {
+        let files = report
+            .records
+            .iter()
+            .filter(|record| match record.action {
+                ExtractAction::Extracted | ExtractAction::Overwritten | ExtractAction::KeptBoth => true,
+                ExtractAction::Unchanged => record.kind != FileKind::Folder,
+                _ => false,
+            })
+            .map(|record| InstalledFile {
+                guid: record.guid.clone(),
+                kind: record.kind,
+                pathname: record.pathname.clone(),
+                hash: record.hash.clone(),
+            })
+            .collect();
+        Self { package: None, files }
+    }
+
+    /// Adds the files of an earlier receipt that `report` skipped or filtered out, the folders
+    /// it found already there, and the originals it wrote a `(n)` copy next to, as the package
+    /// still owns them
+    pub fn keep_previous(&mut self, previous: &Manifest, report: &ExtractReport) {
+        let mut left_alone: HashSet<(FileKind, &str)> = HashSet::new();
+        // Kept-both records carry the pathname of the copy, so originals are found by GUID
+        let mut kept_both: HashSet<(FileKind, &str)> = HashSet::new();
+        for record in &report.records {
+            match record.action {
+                ExtractAction::SkippedExisting | ExtractAction::Filtered => {
+                    left_alone.insert((record.kind, &record.pathname));
+                }
+                ExtractAction::Unchanged if record.kind == FileKind::Folder => {
+                    left_alone.insert((record.kind, &record.pathname));
+                }
+                ExtractAction::KeptBoth => {
+                    kept_both.insert((record.kind, &record.guid));
+                }
+                _ => {}
+            }
+        }
+        let listed: HashSet<(FileKind, &str)> = self.files.iter().map(|file| (file.kind, file.pathname.as_str())).collect();
+
+        let kept: Vec<InstalledFile> = previous
+            .files
+            .iter()
+            .filter(|file| {
+                let by_pathname = (file.kind, file.pathname.as_str());
+                (left_alone.contains(&by_pathname) || kept_both.contains(&(file.kind, file.guid.as_str())))
+                    && !listed.contains(&by_pathname)
+            })
+            .cloned()
+            .collect();
+        self.files.extend(kept);
+    }
+
+    /// Writes the receipt of `report` to `path`, keeping what an existing receipt there
+    /// lists for files this extraction skipped or filtered out
+    pub fn save_receipt(&self, report: &ExtractReport, path: &Path) -> Result<()> {
+        let mut receipt = self.clone();
+        if path.exists() {
+            receipt.keep_previous(&Manifest::load(path)?, report);
+        }
+        receipt.save(path)
+    }
+
+    /// Where the receipt of extracting `package` into `output_path` is kept
+    /// (e.g. `<output>/.unitypackage-receipts/Vendor-1.3.json`)
+    pub fn receipt_path(output_path: &Path, package: &Path) -> PathBuf {
+        Self::named_receipt_path(output_path, &Self::package_name(package))
+    }
+
+    /// Where the receipt of the package named `name` is kept
+    pub fn named_receipt_path(output_path: &Path, name: &str) -> PathBuf {
+        output_path.join(RECEIPT_FOLDER).join(format!("{}.json", name))
+    }
+
+    /// Name a package's receipt is filed under: its file name without extension (`Vendor-1.3`)
+    pub fn package_name(package: &Path) -> String {
+        package.file_stem().unwrap_or(package.as_os_str()).to_string_lossy().into_owned()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Could not read manifest '{}'", path.display()))?;
+        let invalid = || format!("Error: '{}' is not a valid extraction manifest.", path.display());
+
+        let json: Value = serde_json::from_str(&text).with_context(invalid)?;
+        let Some(records) = json.get("files").and_then(Value::as_array) else {
+            bail!(invalid());
+        };
+
+        let mut files = Vec::with_capacity(records.len());
+        for record in records {
+            let field = |name: &str| record.get(name).and_then(Value::as_str);
+            let kind = match field("kind") {
+                Some("asset") => FileKind::Asset,
+                Some("meta") => FileKind::Meta,
+                Some("folder") => FileKind::Folder,
+                _ => bail!(invalid()),
+            };
+            let (Some(guid), Some(pathname)) = (field("guid"), field("pathname")) else {
+                bail!(invalid());
+            };
+            files.push(InstalledFile {
+                guid: guid.to_string(),
+                kind,
+                pathname: pathname.to_string(),
+                hash: field("sha256").map(str::to_string),
+            });
+        }
+        let package = json.get("package").and_then(Value::as_str).map(str::to_string);
+        Ok(Self { package, files })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let files: Vec<Value> = self
+            .files
+            .iter()
+            .map(|file| {
+                json!({
+                    "guid": file.guid,
+                    "kind": file.kind.as_str(),
+                    "pathname": file.pathname,
+                    "sha256": file.hash,
+                })
+            })
+            .collect();
+
+        let mut json = json!({ "files": files });
+        if let Some(package) = &self.package {
+            json["package"] = json!(package);
+        }
+        let text = serde_json::to_string_pretty(&json)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, text + "\n").with_context(|| format!("Could not write manifest '{}'", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::ExtractRecord;
+
+    fn record(guid: &str, kind: FileKind, pathname: &str, action: ExtractAction, hash: Option<&str>) -> ExtractRecord {
+        ExtractRecord {
+            guid: guid.to_string(),
+            kind,
+            pathname: pathname.to_string(),
+            renamed_from: None,
+            destination: PathBuf::from(pathname),
+            action,
+            hash: hash.map(str::to_string),
+        }
+    }
+
+    fn pathnames(manifest: &Manifest) -> Vec<&str> {
+        let mut pathnames: Vec<&str> = manifest.files.iter().map(|file| file.pathname.as_str()).collect();
+        pathnames.sort();
+        pathnames
+    }
+
+    #[test]
+    fn keep_both_receipt_keeps_the_originals() {
+        let first = ExtractReport {
+            records: vec![
+                record("a1", FileKind::Folder, "Assets/Vendor", ExtractAction::Extracted, None),
+                record("a1", FileKind::Meta, "Assets/Vendor.meta", ExtractAction::Extracted, Some("f")),
+                record("b2", FileKind::Asset, "Assets/Vendor/A.cs", ExtractAction::Extracted, Some("1")),
+                record("b2", FileKind::Meta, "Assets/Vendor/A.cs.meta", ExtractAction::Extracted, Some("2")),
+            ],
+            entries: Vec::new(),
+        };
+        let previous = Manifest::from_report(&first);
+
+        let again = ExtractReport {
+            records: vec![
+                record("a1", FileKind::Folder, "Assets/Vendor", ExtractAction::Unchanged, None),
+                record("a1", FileKind::Meta, "Assets/Vendor.meta", ExtractAction::SkippedExisting, None),
+                record("b2", FileKind::Asset, "Assets/Vendor/A (1).cs", ExtractAction::KeptBoth, Some("3")),
+                record("b2", FileKind::Meta, "Assets/Vendor/A (1).cs.meta", ExtractAction::KeptBoth, Some("4")),
+            ],
+            entries: Vec::new(),
+        };
+        let mut receipt = Manifest::from_report(&again);
+        receipt.keep_previous(&previous, &again);
+
+        assert_eq!(
+            pathnames(&receipt),
+            [
+                "Assets/Vendor",
+                "Assets/Vendor.meta",
+                "Assets/Vendor/A (1).cs",
+                "Assets/Vendor/A (1).cs.meta",
+                "Assets/Vendor/A.cs",
+                "Assets/Vendor/A.cs.meta",
+            ]
+        );
+        // The originals keep the hash they were written with, so later changes are noticed
+        let original = receipt.files.iter().find(|file| file.pathname == "Assets/Vendor/A.cs").unwrap();
+        assert_eq!(original.hash.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn overwritten_files_are_not_listed_twice() {
+        let report = ExtractReport {
+            records: vec![record("b2", FileKind::Asset, "Assets/A.cs", ExtractAction::Overwritten, Some("2"))],
+            entries: Vec::new(),
+        };
+        let previous = Manifest {
+            package: None,
+            files: vec![InstalledFile {
+                guid: "b2".to_string(),
+                kind: FileKind::Asset,
+                pathname: "Assets/A.cs".to_string(),
+                hash: Some("1".to_string()),
+            }],
+        };
+        let mut receipt = Manifest::from_report(&report);
+        receipt.keep_previous(&previous, &report);
+
+        assert_eq!(receipt.files.len(), 1);
+        assert_eq!(receipt.files[0].hash.as_deref(), Some("2"));
+    }
+}
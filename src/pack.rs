@@ -0,0 +1,215 @@
+use anyhow::{Context, Result, bail};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::package::meta_guid;
+
+/// A file or folder written to the package
+#[derive(Debug, Clone)]
+pub struct PackedEntry {
+    pub guid: String,
+    pub pathname: String,
+    pub is_folder: bool,
+}
+
+/// A file or folder left out of the package
+#[derive(Debug, Clone)]
+pub struct SkippedEntry {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// There is no `<name>.meta` next to it, so it has no GUID
+    MissingMeta,
+    /// The `.meta` exists but has no valid `guid:` line
+    InvalidMeta,
+    /// Another entry already uses the same GUID
+    DuplicateGuid { other: String },
+}
+
+/// Result of [`pack_directory`]
+#[derive(Debug, Clone, Default)]
+pub struct PackReport {
+    pub packed: Vec<PackedEntry>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// Builds a `.unitypackage` from a directory of Unity assets
+///
+/// Every file and folder needs a `.meta` next to it to provide its GUID, just like in a
+/// Unity project. Pathnames are relative to the directory containing `Assets`, so both a
+/// project root and any folder inside `Assets` can be packed (e.g. `MyProject/Assets/Vendor`
+/// is stored as `Assets/Vendor/...`). Of a project root, only `Assets` is packed. Without an
+/// `Assets` folder in the path, pathnames are relative to `source` itself.
+pub fn pack_directory(source: &Path, output: &Path) -> Result<PackReport> {
+    if !source.is_dir() {
+        bail!("Error: The folder '{}' does not exist.", source.display());
+    }
+
+    let source = source.canonicalize()?;
+    let root = pathname_root(&source);
+
+    let file = File::create(output).with_context(|| format!("Could not create '{}'", output.display()))?;
+    let encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+
+    let mut packer = Packer {
+        root,
+        builder: &mut builder,
+        guids: HashMap::new(),
+        report: PackReport::default(),
+    };
+
+    // When packing a folder inside Assets, the folder itself is part of the package
+    // (except Assets, which never has a .meta)
+    if source != packer.root && packer.pathname(&source) != "Assets" {
+        packer.add(&source)?;
+    }
+    // Of a project root, only Assets holds assets: Library, Temp and the like are left out
+    let assets = source.join("Assets");
+    if source == packer.root && assets.is_dir() {
+        packer.walk(&assets)?;
+    } else {
+        packer.walk(&source)?;
+    }
+    let report = packer.report;
+
+    builder.into_inner()?.finish()?.flush()?;
+    Ok(report)
+}
+
+/// Directory that pathnames are relative to: the parent of the closest `Assets` ancestor
+fn pathname_root(source: &Path) -> PathBuf {
+    source
+        .ancestors()
+        .find(|dir| dir.file_name().is_some_and(|name| name == "Assets"))
+        .and_then(Path::parent)
+        .unwrap_or(source)
+        .to_path_buf()
+}
+
+/// Files and folders Unity ignores when importing
+fn is_ignored(name: &str) -> bool {
+    name.starts_with('.') || name.ends_with('~') || name.ends_with(".meta")
+}
+
+struct Packer<'a, W: Write> {
+    root: PathBuf,
+    builder: &'a mut tar::Builder<W>,
+    /// GUID -> pathname of every entry written so far
+    guids: HashMap<String, String>,
+    report: PackReport,
+}
+
+impl<W: Write> Packer<'_, W> {
+    fn walk(&mut self, dir: &Path) -> Result<()> {
+        let mut children: Vec<PathBuf> = fs::read_dir(dir)
+            .with_context(|| format!("Could not read folder '{}'", dir.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        // Sorted for reproducible packages
+        children.sort();
+
+        for child in children {
+            let name = child.file_name().unwrap_or_default().to_string_lossy();
+            if is_ignored(&name) {
+                continue;
+            }
+
+            // The Assets folder itself never has a .meta
+            if self.pathname(&child) != "Assets" {
+                self.add(&child)?;
+            }
+            if child.is_dir() {
+                self.walk(&child)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a single file or folder as `<guid>/pathname`, `<guid>/asset.meta` and `<guid>/asset`
+    fn add(&mut self, path: &Path) -> Result<()> {
+        let mut meta_path = path.as_os_str().to_owned();
+        meta_path.push(".meta");
+        let meta_path = PathBuf::from(meta_path);
+
+        let Ok(meta) = fs::read(&meta_path) else {
+            self.skip(path, SkipReason::MissingMeta);
+            return Ok(());
+        };
+        let Some(guid) = meta_guid(&meta) else {
+            self.skip(path, SkipReason::InvalidMeta);
+            return Ok(());
+        };
+        if let Some(other) = self.guids.get(&guid) {
+            let other = other.clone();
+            self.skip(path, SkipReason::DuplicateGuid { other });
+            return Ok(());
+        }
+
+        let pathname = self.pathname(path);
+        let is_folder = path.is_dir();
+        let mtime = modified_secs(path);
+
+        // `pathname` first, so the package can be extracted without buffering the other files
+        append(self.builder, &format!("{}/pathname", guid), pathname.as_bytes(), pathname.len() as u64, mtime)?;
+        append(self.builder, &format!("{}/asset.meta", guid), meta.as_slice(), meta.len() as u64, modified_secs(&meta_path))?;
+        if !is_folder {
+            let file = File::open(path).with_context(|| format!("Could not read '{}'", path.display()))?;
+            let size = file.metadata()?.len();
+            append(self.builder, &format!("{}/asset", guid), file, size, mtime)?;
+        }
+
+        self.guids.insert(guid.clone(), pathname.clone());
+        self.report.packed.push(PackedEntry { guid, pathname, is_folder });
+        Ok(())
+    }
+
+    /// Pathname stored in the package, always with `/` separators
+    fn pathname(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn skip(&mut self, path: &Path, reason: SkipReason) {
+        self.report.skipped.push(SkippedEntry {
+            path: path.to_path_buf(),
+            reason,
+        });
+    }
+}
+
+fn modified_secs(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn append<W: Write>(builder: &mut tar::Builder<W>, name: &str, data: impl Read, size: u64, mtime: u64) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    header.set_entry_type(tar::EntryType::Regular);
+    builder
+        .append_data(&mut header, name, data)
+        .with_context(|| format!("Could not add '{}' to the package", name))?;
+    Ok(())
+}
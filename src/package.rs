@@ -0,0 +1,298 @@
+use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::cat;
+use crate::convert::{self, TarWriter, ZipOutput};
+use crate::deps::{self, DependencyGraph};
+use crate::diff::{self, EntryDigest};
+use crate::extract::{self, ExtractOptions, ExtractRecord, ExtractReport, FileKind};
+use crate::filter::PathFilter;
+use crate::manifest::Manifest;
+use crate::preview::{self, PreviewRecord};
+use crate::progress::{CountingReader, Progress, ProgressTracker};
+use crate::stream::{EntrySummary, sorted_entries};
+use crate::upgrade::{self, UpgradeOptions, UpgradeReport};
+use crate::verify::{self, VerifyReport};
+
+/// Archive type produced when opening a `.unitypackage` (tar inside gzip)
+pub(crate) type PackageArchive = tar::Archive<GzDecoder<BufReader<CountingReader<Box<dyn Read>>>>>;
+
+/// Summary of a single GUID directory inside the package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageEntry {
+    pub guid: String,
+    pub pathname: String,
+    pub has_asset: bool,
+    pub has_meta: bool,
+    pub has_preview: bool,
+    /// Folder entry: no asset and `folderAsset: yes` in its meta
+    pub is_folder: bool,
+    /// Size in bytes of the `asset` file (0 when there is none)
+    pub size: u64,
+}
+
+/// A `.unitypackage` file on disk
+///
+/// Every operation streams the archive from the start, so the package is never
+/// unpacked to a temporary directory.
+#[derive(Debug, Clone)]
+pub struct UnityPackage {
+    path: PathBuf,
+    source: Source,
+}
+
+/// Where the package bytes are read from
+#[derive(Debug, Clone)]
+enum Source {
+    File,
+    /// Standard input, which can only be streamed once
+    Stdin { consumed: Arc<AtomicBool> },
+}
+
+impl UnityPackage {
+    /// Opens a package, failing if the file does not exist
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            bail!("Error: The file '{}' does not exist.", path.display());
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            source: Source::File,
+        })
+    }
+
+    /// Reads the package from standard input
+    ///
+    /// Standard input can only be streamed once, so operations that read the package twice
+    /// (such as [`UnityPackage::upgrade`]) fail, as does any second operation.
+    pub fn stdin() -> Self {
+        Self {
+            path: PathBuf::from("-"),
+            source: Source::Stdin {
+                consumed: Arc::new(AtomicBool::new(false)),
+            },
+        }
+    }
+
+    /// Path of the package file, `-` when reading from standard input
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_stdin(&self) -> bool {
+        matches!(self.source, Source::Stdin { .. })
+    }
+
+    /// Size in bytes of the package file, unknown when reading from standard input
+    pub fn size(&self) -> Result<Option<u64>> {
+        match self.source {
+            Source::File => Ok(Some(fs::metadata(&self.path).context("Could not open .unitypackage file")?.len())),
+            Source::Stdin { .. } => Ok(None),
+        }
+    }
+
+    /// Lists every entry that has a `pathname`, sorted by pathname
+    pub fn entries(&self) -> Result<Vec<PackageEntry>> {
+        let mut summaries: HashMap<String, EntrySummary> = HashMap::new();
+        let mut archive = self.archive()?;
+
+        for entry in archive.entries().context("Error reading package contents")? {
+            let mut entry = entry.context("Error reading package contents")?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let Some((guid, name)) = split_entry_path(&entry.path()?) else {
+                continue;
+            };
+            let size = entry.header().size()?;
+
+            let summary = summaries.entry(guid).or_default();
+            match name.as_str() {
+                "pathname" => summary.pathname = Some(read_pathname(&mut entry)?),
+                "asset.meta" => {
+                    let mut meta = Vec::new();
+                    entry.read_to_end(&mut meta)?;
+                    summary.add(&name, size, Some(&meta));
+                }
+                _ => summary.add(&name, size, None),
+            }
+        }
+
+        Ok(sorted_entries(summaries))
+    }
+
+    /// Extracts every asset (and its `.meta` unless disabled) into `output_path`
+    pub fn extract(&self, output_path: &Path, options: &ExtractOptions) -> Result<ExtractReport> {
+        self.extract_with(output_path, options, |_| {})
+    }
+
+    /// Same as [`UnityPackage::extract`], calling `on_record` as soon as each asset is handled
+    pub fn extract_with(
+        &self,
+        output_path: &Path,
+        options: &ExtractOptions,
+        on_record: impl FnMut(&ExtractRecord),
+    ) -> Result<ExtractReport> {
+        self.extract_with_progress(output_path, options, on_record, |_| {})
+    }
+
+    /// Same as [`UnityPackage::extract_with`], also calling `on_progress` after each archive entry
+    /// and once more when the whole package has been read
+    pub fn extract_with_progress(
+        &self,
+        output_path: &Path,
+        options: &ExtractOptions,
+        on_record: impl FnMut(&ExtractRecord),
+        on_progress: impl FnMut(&Progress),
+    ) -> Result<ExtractReport> {
+        let (archive, bytes_read) = self.counted_archive()?;
+        let total_bytes = self.size()?;
+        let progress = ProgressTracker::new(bytes_read, total_bytes, on_progress);
+        extract::extract_archive(archive, output_path, options, on_record, progress)
+    }
+
+    /// Writes the selected assets (and their `.meta` unless disabled) to `out` as a plain tar
+    /// stream whose entries are named by real pathname instead of GUID
+    ///
+    /// Filters and the path traversal check apply as for [`UnityPackage::extract`]; the conflict
+    /// policy, dry run, atomic and jobs options do not. Records' destinations are the entry names.
+    pub fn write_tar(&self, out: impl Write, options: &ExtractOptions) -> Result<ExtractReport> {
+        let mut writer = TarWriter::new(out);
+        let report = convert::convert_archive(self.archive()?, &mut writer, options, false)?;
+        writer.finish()?;
+        Ok(report)
+    }
+
+    /// Writes the selected assets (and their `.meta` unless disabled) to `out` as a zip archive
+    /// whose entries are named by real pathname, with each thumbnail as `<pathname>.png` if
+    /// `previews` is set
+    ///
+    /// Options apply as for [`UnityPackage::write_tar`]. A file whose name is already in the
+    /// archive (two GUIDs with the same pathname) is left out as
+    /// [`ExtractAction::SkippedExisting`](crate::ExtractAction::SkippedExisting).
+    pub fn write_zip(&self, out: impl Write + Seek, options: &ExtractOptions, previews: bool) -> Result<ExtractReport> {
+        let mut writer = ZipOutput::new(out);
+        let report = convert::convert_archive(self.archive()?, &mut writer, options, previews)?;
+        writer.finish()?;
+        Ok(report)
+    }
+
+    /// Applies this package over a previous extraction of another version, described by `previous`
+    ///
+    /// Files removed upstream are deleted, files whose GUID moved follow it, and files left
+    /// untouched locally are updated. Files changed on both sides are reported as conflicts
+    /// and left as they are. The package is read twice: once to hash it, once to write.
+    pub fn upgrade(&self, output_path: &Path, previous: &Manifest, options: &UpgradeOptions) -> Result<UpgradeReport> {
+        upgrade::upgrade_archive(self.digest()?, self.archive()?, output_path, previous, options)
+    }
+
+    /// Writes the thumbnail of every asset selected by `filter` as `<output_path>/<pathname>.png`
+    ///
+    /// Returns one record per selected asset (folders excluded), with or without a preview.
+    pub fn extract_previews(&self, output_path: &Path, filter: &PathFilter) -> Result<Vec<PreviewRecord>> {
+        preview::extract_previews(self.archive()?, output_path, filter)
+    }
+
+    /// Streams the `asset` (or, for [`FileKind::Meta`], `asset.meta`) of the entry with GUID or
+    /// pathname `asset` into `out`, returning `false` if the package has no such file
+    ///
+    /// Decompression stops as soon as the file has been copied.
+    pub fn copy_file(&self, asset: &str, kind: FileKind, mut out: impl Write) -> Result<bool> {
+        cat::copy_file(self.archive()?, asset, kind, &mut out)
+    }
+
+    /// Scans the text-serialized assets and builds the GUID dependency graph
+    pub fn dependency_graph(&self) -> Result<DependencyGraph> {
+        deps::build_graph(self.archive()?)
+    }
+
+    /// Content hashes of every entry with a `pathname`, keyed by GUID
+    pub fn digest(&self) -> Result<BTreeMap<String, EntryDigest>> {
+        diff::digest_archive(self.archive()?)
+    }
+
+    /// Reads the `asset` contents of the given GUIDs into memory, leaving out binary files
+    /// and files over [`diff::MAX_TEXT_DIFF_BYTES`]
+    pub fn text_asset_contents(&self, guids: &HashSet<String>) -> Result<HashMap<String, Vec<u8>>> {
+        diff::read_text_assets(self.archive()?, guids)
+    }
+
+    /// Reads the whole package checking its integrity and layout
+    ///
+    /// Damaged archives are reported as [`IssueCategory::Corrupt`](crate::verify::IssueCategory::Corrupt)
+    /// issues; an error is only returned if the file cannot be opened.
+    pub fn verify(&self) -> Result<VerifyReport> {
+        verify::verify_archive(self.archive()?)
+    }
+
+    pub(crate) fn archive(&self) -> Result<PackageArchive> {
+        Ok(self.counted_archive()?.0)
+    }
+
+    /// Opens the archive along with a counter of the compressed bytes read from the file
+    pub(crate) fn counted_archive(&self) -> Result<(PackageArchive, Arc<AtomicU64>)> {
+        let input: Box<dyn Read> = match &self.source {
+            Source::File => Box::new(File::open(&self.path).context("Could not open .unitypackage file")?),
+            Source::Stdin { consumed } => {
+                if consumed.swap(true, Ordering::SeqCst) {
+                    bail!("Error: A package read from standard input can only be read once.");
+                }
+                Box::new(io::stdin())
+            }
+        };
+        let bytes_read = Arc::new(AtomicU64::new(0));
+        let reader = CountingReader::new(input, Arc::clone(&bytes_read));
+        Ok((tar::Archive::new(GzDecoder::new(BufReader::new(reader))), bytes_read))
+    }
+}
+
+/// Splits an archive entry path into its GUID and file name (`<guid>/<name>`)
+pub(crate) fn split_entry_path(path: &Path) -> Option<(String, String)> {
+    let mut parts = path
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .map(|c| c.as_os_str().to_string_lossy().into_owned());
+
+    let guid = parts.next()?;
+    let name = parts.next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((guid, name))
+}
+
+/// Reads the first line of a `pathname` entry
+pub(crate) fn read_pathname(reader: impl Read) -> Result<String> {
+    let mut reader = BufReader::new(reader);
+    let mut pathname = String::new();
+    reader.read_line(&mut pathname)?;
+    Ok(pathname.trim_end().to_string())
+}
+
+/// Whether an `asset.meta` describes a folder (`folderAsset: yes`)
+pub(crate) fn is_folder_meta(meta: &[u8]) -> bool {
+    String::from_utf8_lossy(meta)
+        .lines()
+        .any(|line| line.trim() == "folderAsset: yes")
+}
+
+/// GUID declared by an `asset.meta` (`guid: <32 hex digits>`)
+pub(crate) fn meta_guid(meta: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(meta).lines().find_map(|line| {
+        let guid = line.trim().strip_prefix("guid:")?.trim();
+        is_valid_guid(guid).then(|| guid.to_string())
+    })
+}
+
+/// Whether `guid` has Unity's GUID format: 32 lowercase hexadecimal digits
+pub(crate) fn is_valid_guid(guid: &str) -> bool {
+    guid.len() == 32 && guid.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
@@ -0,0 +1,174 @@
+use anyhow::{Context, Error, Result};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::hash::copy_hashed;
+
+/// Files up to this size are read into memory and handed to a worker; bigger ones are
+/// written by the reading thread, so memory stays bounded by the queue length.
+const MAX_JOB_BYTES: u64 = 1024 * 1024;
+
+struct Job {
+    /// Position in submission order, so the earliest failure is the one reported
+    sequence: usize,
+    destination: PathBuf,
+    data: Vec<u8>,
+}
+
+/// State shared with the workers
+#[derive(Default)]
+struct Shared {
+    /// Number of jobs submitted but not finished yet
+    in_flight: Mutex<usize>,
+    idle: Condvar,
+    errors: Mutex<Vec<(usize, Error)>>,
+}
+
+/// Bounded pool of threads writing small files (and creating their folders) in parallel
+///
+/// Every decision (conflicts, renames, log lines) is still taken by the reading thread in
+/// archive order; workers only perform the writes. Destinations with a pending write count
+/// as occupied, and touching one from the reading thread first waits for the pool to drain.
+pub(crate) struct WritePool {
+    sender: Option<SyncSender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    shared: Arc<Shared>,
+    /// Destinations submitted since the pool was last drained
+    pending: HashSet<PathBuf>,
+    next_sequence: usize,
+}
+
+impl WritePool {
+    pub(crate) fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(threads * 2);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let shared = Arc::new(Shared::default());
+
+        let workers = (0..threads)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || work(&receiver, &shared))
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            workers,
+            shared,
+            pending: HashSet::new(),
+            next_sequence: 0,
+        }
+    }
+
+    /// Whether a write to `destination` has been submitted and may not have finished yet
+    pub(crate) fn is_pending(&self, destination: &Path) -> bool {
+        self.pending.contains(destination)
+    }
+
+    /// Writes `asset` to `destination` (creating its folder), returning the hash of its contents
+    pub(crate) fn write(&mut self, destination: &Path, asset: &mut impl Read) -> Result<String> {
+        self.check_errors()?;
+
+        let mut data = Vec::new();
+        asset.take(MAX_JOB_BYTES + 1).read_to_end(&mut data)?;
+
+        if data.len() as u64 > MAX_JOB_BYTES || self.is_pending(destination) {
+            self.settle(destination)?;
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut out = File::create(destination)
+                .with_context(|| format!("Could not create '{}'", destination.display()))?;
+            return Ok(copy_hashed(&mut data.as_slice().chain(asset), &mut out)?);
+        }
+
+        let hash = copy_hashed(&mut data.as_slice(), &mut io::sink())?;
+        *self.shared.in_flight.lock().expect("Poisoned lock") += 1;
+        self.pending.insert(destination.to_path_buf());
+
+        let job = Job {
+            sequence: self.next_sequence,
+            destination: destination.to_path_buf(),
+            data,
+        };
+        self.next_sequence += 1;
+        self.sender
+            .as_ref()
+            .expect("Pool already finished")
+            .send(job)
+            .expect("Write workers stopped");
+        Ok(hash)
+    }
+
+    /// Waits for every pending write if one of them targets `destination`
+    pub(crate) fn settle(&mut self, destination: &Path) -> Result<()> {
+        if !self.is_pending(destination) {
+            return Ok(());
+        }
+
+        let mut in_flight = self.shared.in_flight.lock().expect("Poisoned lock");
+        while *in_flight > 0 {
+            in_flight = self.shared.idle.wait(in_flight).expect("Poisoned lock");
+        }
+        drop(in_flight);
+
+        self.pending.clear();
+        self.check_errors()
+    }
+
+    /// Waits for the remaining writes and stops the workers
+    pub(crate) fn finish(mut self) -> Result<()> {
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            worker.join().expect("Write worker panicked");
+        }
+        self.check_errors()
+    }
+
+    /// Returns the earliest failed write, if any
+    fn check_errors(&self) -> Result<()> {
+        let mut errors = self.shared.errors.lock().expect("Poisoned lock");
+        match errors.iter().enumerate().min_by_key(|(_, (sequence, _))| *sequence).map(|(i, _)| i) {
+            Some(i) => Err(errors.swap_remove(i).1),
+            None => Ok(()),
+        }
+    }
+}
+
+fn work(receiver: &Mutex<Receiver<Job>>, shared: &Shared) {
+    loop {
+        // The lock is only held while waiting for the next job
+        let job = receiver.lock().expect("Poisoned lock").recv();
+        let Ok(job) = job else {
+            break;
+        };
+
+        if let Err(e) = write_job(&job) {
+            shared.errors.lock().expect("Poisoned lock").push((job.sequence, e));
+        }
+
+        let mut in_flight = shared.in_flight.lock().expect("Poisoned lock");
+        *in_flight -= 1;
+        if *in_flight == 0 {
+            shared.idle.notify_all();
+        }
+    }
+}
+
+fn write_job(job: &Job) -> Result<()> {
+    if let Some(parent) = job.destination.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Could not create folder '{}'", parent.display()))?;
+    }
+    let mut out = File::create(&job.destination)
+        .with_context(|| format!("Could not create '{}'", job.destination.display()))?;
+    out.write_all(&job.data)
+        .with_context(|| format!("Could not write '{}'", job.destination.display()))?;
+    Ok(())
+}
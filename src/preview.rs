@@ -0,0 +1,116 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::extract::{OutputResolver, write_new};
+use crate::filter::PathFilter;
+use crate::package::{PackageArchive, is_folder_meta, read_pathname, split_entry_path};
+
+/// An asset of the package and its extracted thumbnail
+#[derive(Debug, Clone)]
+pub struct PreviewRecord {
+    pub guid: String,
+    pub pathname: String,
+    /// Size in bytes of the `asset` file
+    pub size: u64,
+    /// Where `preview.png` was written, relative to the output directory (`<pathname>.png`),
+    /// or `None` if the package has no preview for this asset
+    pub preview: Option<String>,
+}
+
+#[derive(Default)]
+struct PendingPreview {
+    pathname: Option<String>,
+    has_asset: bool,
+    is_folder: bool,
+    size: u64,
+    /// Thumbnails that arrived before their pathname; they are small enough to keep in memory
+    data: Option<Vec<u8>>,
+    preview: Option<String>,
+}
+
+/// Streams the archive once, writing each `preview.png` as `<output>/<pathname>.png`
+pub(crate) fn extract_previews(
+    mut archive: PackageArchive,
+    output_path: &Path,
+    filter: &PathFilter,
+) -> Result<Vec<PreviewRecord>> {
+    let resolver = OutputResolver::new(output_path);
+    let mut pending: HashMap<String, PendingPreview> = HashMap::new();
+
+    for entry in archive.entries().context("Error reading package contents")? {
+        let mut entry = entry.context("Error reading package contents")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let Some((guid, name)) = split_entry_path(&entry.path()?) else {
+            continue;
+        };
+
+        let state = pending.entry(guid).or_default();
+        match name.as_str() {
+            "pathname" => {
+                let pathname = read_pathname(&mut entry)?;
+                if let Some(data) = state.data.take() {
+                    state.preview = write_preview(&resolver, filter, &pathname, data.as_slice())?;
+                }
+                state.pathname = Some(pathname);
+            }
+            "preview.png" => match &state.pathname {
+                Some(pathname) => state.preview = write_preview(&resolver, filter, pathname, &mut entry)?,
+                None => {
+                    let mut data = Vec::new();
+                    entry.read_to_end(&mut data)?;
+                    state.data = Some(data);
+                }
+            },
+            "asset" => {
+                state.has_asset = true;
+                state.size = entry.header().size()?;
+            }
+            "asset.meta" => {
+                let mut meta = Vec::new();
+                entry.read_to_end(&mut meta)?;
+                state.is_folder = is_folder_meta(&meta);
+            }
+            _ => {}
+        }
+    }
+
+    let mut records: Vec<PreviewRecord> = pending
+        .into_iter()
+        .filter(|(_, state)| state.has_asset || !state.is_folder)
+        .filter_map(|(guid, state)| {
+            let pathname = state.pathname?;
+            filter.matches(&pathname).then_some(PreviewRecord {
+                guid,
+                pathname,
+                size: state.size,
+                preview: state.preview,
+            })
+        })
+        .collect();
+    records.sort_by(|a, b| a.pathname.cmp(&b.pathname));
+    Ok(records)
+}
+
+/// Writes a thumbnail as `<pathname>.png`, returning its output pathname if it was selected
+fn write_preview(
+    resolver: &OutputResolver,
+    filter: &PathFilter,
+    pathname: &str,
+    mut data: impl Read,
+) -> Result<Option<String>> {
+    let resolved = resolver.resolve(&format!("{}.png", pathname));
+    if !resolved.inside || !filter.matches(pathname) {
+        return Ok(None);
+    }
+    if let Some(parent) = resolved.destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_new(&resolved.destination, &mut data, false)?;
+    Ok(Some(resolved.pathname))
+}
@@ -0,0 +1,101 @@
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// How far an operation has read into a package
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Compressed bytes of the package file read so far
+    pub bytes_read: u64,
+    /// Size of the package file, unknown when reading from standard input
+    pub total_bytes: Option<u64>,
+    /// Archive entries (`pathname`, `asset`, `asset.meta`, ...) read so far
+    pub entries: u64,
+    /// Time since the operation started
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Fraction of the package read, from 0.0 to 1.0, when its size is known
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total_bytes {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.bytes_read as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+
+    /// Compressed bytes read per second
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 { self.bytes_read as f64 / seconds } else { 0.0 }
+    }
+
+    /// Estimated time left at the current throughput, once there is enough data for it
+    pub fn eta(&self) -> Option<Duration> {
+        let throughput = self.throughput();
+        if throughput <= 0.0 || self.bytes_read == 0 {
+            return None;
+        }
+        let remaining = self.total_bytes?.saturating_sub(self.bytes_read) as f64;
+        Some(Duration::from_secs_f64(remaining / throughput))
+    }
+}
+
+/// Reader that counts the bytes read through it into a shared counter
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R> CountingReader<R> {
+    pub(crate) fn new(inner: R, count: Arc<AtomicU64>) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+/// Turns the byte counter of an archive into [`Progress`] reports
+pub(crate) struct ProgressTracker<P> {
+    bytes_read: Arc<AtomicU64>,
+    total_bytes: Option<u64>,
+    entries: u64,
+    start: Instant,
+    on_progress: P,
+}
+
+impl<P: FnMut(&Progress)> ProgressTracker<P> {
+    pub(crate) fn new(bytes_read: Arc<AtomicU64>, total_bytes: Option<u64>, on_progress: P) -> Self {
+        Self {
+            bytes_read,
+            total_bytes,
+            entries: 0,
+            start: Instant::now(),
+            on_progress,
+        }
+    }
+
+    /// Counts one more archive entry and reports
+    pub(crate) fn entry(&mut self) {
+        self.entries += 1;
+        self.report();
+    }
+
+    pub(crate) fn report(&mut self) {
+        let progress = Progress {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            total_bytes: self.total_bytes,
+            entries: self.entries,
+            elapsed: self.start.elapsed(),
+        };
+        (self.on_progress)(&progress);
+    }
+}
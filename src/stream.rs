@@ -0,0 +1,293 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::extract::ExtractOptions;
+use crate::package::{PackageArchive, PackageEntry, is_folder_meta, read_pathname, split_entry_path};
+
+/// Upper bound for file bytes held in memory while waiting for their `pathname`.
+/// Anything beyond this is spilled to an anonymous temporary file.
+pub(crate) const MAX_BUFFERED_BYTES: u64 = 64 * 1024 * 1024;
+
+/// File contents that arrived before the `pathname` of their GUID
+pub(crate) enum PendingAsset {
+    Memory(Vec<u8>),
+    Spilled(File),
+}
+
+impl PendingAsset {
+    /// Reads `size` bytes into memory, or into an anonymous temporary file past `memory_limit`
+    pub(crate) fn read(reader: &mut impl Read, size: u64, memory_limit: u64) -> Result<Self> {
+        if size <= memory_limit {
+            let mut data = Vec::with_capacity(size as usize);
+            reader.read_to_end(&mut data)?;
+            Ok(PendingAsset::Memory(data))
+        } else {
+            let mut spill = tempfile::tempfile().context("Could not create spill file")?;
+            io::copy(reader, &mut spill)?;
+            Ok(PendingAsset::Spilled(spill))
+        }
+    }
+
+    /// Bytes held in memory
+    pub(crate) fn len(&self) -> u64 {
+        match self {
+            PendingAsset::Memory(data) => data.len() as u64,
+            PendingAsset::Spilled(_) => 0,
+        }
+    }
+
+    /// Reader over the buffered contents, from the start
+    pub(crate) fn into_reader(self) -> Result<Box<dyn Read>> {
+        match self {
+            PendingAsset::Memory(data) => Ok(Box::new(io::Cursor::new(data))),
+            PendingAsset::Spilled(mut spill) => {
+                spill.seek(SeekFrom::Start(0))?;
+                Ok(Box::new(spill))
+            }
+        }
+    }
+}
+
+/// Size and modification time of a file, taken from its entry in the package
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FileInfo {
+    pub(crate) size: u64,
+    pub(crate) mtime: u64,
+}
+
+/// What the archive told about a GUID directory, turned into a [`PackageEntry`] at the end
+#[derive(Default)]
+pub(crate) struct EntrySummary {
+    pub(crate) pathname: Option<String>,
+    pub(crate) has_asset: bool,
+    pub(crate) has_meta: bool,
+    pub(crate) has_preview: bool,
+    pub(crate) is_folder: bool,
+    pub(crate) size: u64,
+}
+
+impl EntrySummary {
+    /// Notes one archive file of the GUID directory (the `pathname` is set by the caller)
+    pub(crate) fn add(&mut self, name: &str, size: u64, meta: Option<&[u8]>) {
+        match name {
+            "asset" => {
+                self.has_asset = true;
+                self.size = size;
+            }
+            "asset.meta" => {
+                self.has_meta = true;
+                self.is_folder = meta.is_some_and(is_folder_meta);
+            }
+            "preview.png" => self.has_preview = true,
+            _ => {}
+        }
+    }
+
+    /// Entries without a `pathname` cannot be placed anywhere and are left out
+    fn into_entry(self, guid: String) -> Option<PackageEntry> {
+        Some(PackageEntry {
+            guid,
+            pathname: self.pathname?,
+            has_asset: self.has_asset,
+            has_meta: self.has_meta,
+            has_preview: self.has_preview,
+            is_folder: self.is_folder && !self.has_asset,
+            size: self.size,
+        })
+    }
+}
+
+/// Every summarized GUID directory with a `pathname`, sorted by pathname
+pub(crate) fn sorted_entries(summaries: impl IntoIterator<Item = (String, EntrySummary)>) -> Vec<PackageEntry> {
+    let mut entries: Vec<PackageEntry> = summaries
+        .into_iter()
+        .filter_map(|(guid, summary)| summary.into_entry(guid))
+        .collect();
+    entries.sort_by(|a, b| a.pathname.cmp(&b.pathname));
+    entries
+}
+
+/// Receives the files of a package in archive order, as soon as the `pathname` of their GUID is known
+pub(crate) trait PackageVisitor {
+    fn asset(&mut self, guid: &str, pathname: &str, selected: bool, info: FileInfo, data: &mut dyn Read) -> Result<()>;
+
+    /// `asset.meta`, which also tells whether the entry is a folder
+    fn meta(&mut self, guid: &str, pathname: &str, selected: bool, is_folder: bool, info: FileInfo, meta: &[u8]) -> Result<()>;
+
+    /// `preview.png`, only read when [`PackageVisitor::wants_previews`] says so
+    fn preview(&mut self, _guid: &str, _pathname: &str, _selected: bool, _info: FileInfo, _data: &mut dyn Read) -> Result<()> {
+        Ok(())
+    }
+
+    fn wants_previews(&self) -> bool {
+        false
+    }
+}
+
+/// Files of a GUID directory that arrived before its `pathname`
+#[derive(Default)]
+struct PendingFiles {
+    summary: EntrySummary,
+    asset: Option<(PendingAsset, FileInfo)>,
+    /// Metas are small and always read into memory, since they are needed to detect folders
+    meta: Option<(Vec<u8>, FileInfo)>,
+    preview: Option<(PendingAsset, FileInfo)>,
+}
+
+/// Decides which files `options` selects. A folder that does not match a pattern itself is only
+/// selected when a selected asset is inside it, which may not be known before the end of the archive.
+struct Selection<'a> {
+    options: &'a ExtractOptions,
+    /// Every folder holding a selected file so far
+    selected_dirs: HashSet<String>,
+    /// Folders not selected yet, with their meta, in archive order
+    deferred_folders: Vec<(String, String, FileInfo, Vec<u8>)>,
+}
+
+impl Selection<'_> {
+    /// Whether an asset (or its meta, preview) is selected, noting the folders it is in
+    fn file(&mut self, guid: &str, pathname: &str) -> bool {
+        let selected = self.options.selects(guid, pathname);
+        if selected {
+            let mut path = pathname;
+            while let Some((parent, _)) = path.rsplit_once('/') {
+                if !self.selected_dirs.insert(parent.to_string()) {
+                    break;
+                }
+                path = parent;
+            }
+        }
+        selected
+    }
+
+    fn folder(&self, guid: &str, pathname: &str) -> bool {
+        self.options.selects_folder(guid, pathname, self.selected_dirs.contains(pathname))
+    }
+
+    /// Hands a meta over, holding back folders that are not selected yet
+    fn meta(&mut self, visitor: &mut impl PackageVisitor, guid: &str, pathname: &str, info: FileInfo, meta: Vec<u8>) -> Result<()> {
+        if !is_folder_meta(&meta) {
+            let selected = self.file(guid, pathname);
+            return visitor.meta(guid, pathname, selected, false, info, &meta);
+        }
+        if self.folder(guid, pathname) {
+            return visitor.meta(guid, pathname, true, true, info, &meta);
+        }
+        self.deferred_folders.push((guid.to_string(), pathname.to_string(), info, meta));
+        Ok(())
+    }
+
+    /// Hands the held back folders over once every asset has been seen
+    fn finish(mut self, visitor: &mut impl PackageVisitor) -> Result<()> {
+        for (guid, pathname, info, meta) in std::mem::take(&mut self.deferred_folders) {
+            let selected = self.folder(&guid, &pathname);
+            visitor.meta(&guid, &pathname, selected, true, info, &meta)?;
+        }
+        Ok(())
+    }
+}
+
+/// Streams the archive once, grouping its files by GUID and handing them to `visitor` with
+/// whether `options` selects them. Files that come before the `pathname` of their GUID are
+/// buffered (spilling to a temporary file) until it shows up, and folders that might not be
+/// selected until the end of the archive. `on_entry` is called for every archive entry read.
+/// Returns the entries of the package, sorted by pathname.
+pub(crate) fn visit_package(
+    archive: &mut PackageArchive,
+    options: &ExtractOptions,
+    visitor: &mut impl PackageVisitor,
+    mut on_entry: impl FnMut(),
+) -> Result<Vec<PackageEntry>> {
+    let mut pending: HashMap<String, PendingFiles> = HashMap::new();
+    let mut selection = Selection {
+        options,
+        selected_dirs: HashSet::new(),
+        deferred_folders: Vec::new(),
+    };
+    let mut buffered_bytes: u64 = 0;
+    let previews = visitor.wants_previews();
+
+    for entry in archive.entries().context("Error reading package contents")? {
+        let mut entry = entry.context("Error reading package contents")?;
+        on_entry();
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let Some((guid, name)) = split_entry_path(&entry.path()?) else {
+            continue;
+        };
+        let info = FileInfo {
+            size: entry.header().size()?,
+            mtime: entry.header().mtime().unwrap_or(0),
+        };
+        let state = pending.entry(guid.clone()).or_default();
+
+        match name.as_str() {
+            "pathname" => {
+                let pathname = read_pathname(&mut entry)?;
+
+                // Flush files that were waiting for this pathname
+                if let Some((data, info)) = state.asset.take() {
+                    buffered_bytes -= data.len();
+                    let selected = selection.file(&guid, &pathname);
+                    visitor.asset(&guid, &pathname, selected, info, &mut data.into_reader()?)?;
+                }
+                if let Some((meta, info)) = state.meta.take() {
+                    buffered_bytes -= meta.len() as u64;
+                    selection.meta(visitor, &guid, &pathname, info, meta)?;
+                }
+                if let Some((data, info)) = state.preview.take() {
+                    buffered_bytes -= data.len();
+                    let selected = selection.file(&guid, &pathname);
+                    visitor.preview(&guid, &pathname, selected, info, &mut data.into_reader()?)?;
+                }
+                state.summary.pathname = Some(pathname);
+            }
+            "asset.meta" => {
+                let mut meta = Vec::new();
+                entry.read_to_end(&mut meta)?;
+                state.summary.add(&name, info.size, Some(&meta));
+
+                if let Some(pathname) = &state.summary.pathname {
+                    selection.meta(visitor, &guid, pathname, info, meta)?;
+                } else {
+                    buffered_bytes += meta.len() as u64;
+                    state.meta = Some((meta, info));
+                }
+            }
+            "asset" | "preview.png" => {
+                state.summary.add(&name, info.size, None);
+                let is_asset = name == "asset";
+                if !is_asset && !previews {
+                    continue;
+                }
+
+                if let Some(pathname) = &state.summary.pathname {
+                    let selected = selection.file(&guid, pathname);
+                    if is_asset {
+                        visitor.asset(&guid, pathname, selected, info, &mut entry)?;
+                    } else {
+                        visitor.preview(&guid, pathname, selected, info, &mut entry)?;
+                    }
+                    continue;
+                }
+
+                // The pathname has not been seen yet: keep the file until it shows up
+                let data = PendingAsset::read(&mut entry, info.size, MAX_BUFFERED_BYTES.saturating_sub(buffered_bytes))?;
+                buffered_bytes += data.len();
+                if is_asset {
+                    state.asset = Some((data, info));
+                } else {
+                    state.preview = Some((data, info));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    selection.finish(visitor)?;
+    Ok(sorted_entries(pending.into_iter().map(|(guid, state)| (guid, state.summary))))
+}
@@ -0,0 +1,192 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tempfile::TempPath;
+
+use crate::hash::{copy_hashed, hash_file};
+
+/// Writes staged next to their destination, made visible all at once by [`Transaction::commit`]
+///
+/// Staged files and backups start with a dot so Unity ignores them while they exist.
+/// Dropping an uncommitted transaction deletes the staged files; [`Transaction::rollback`]
+/// also removes the folders it created.
+#[derive(Default)]
+pub(crate) struct Transaction {
+    /// Staged files are closed once written, so large packages do not run out of descriptors
+    staged: Vec<(TempPath, PathBuf)>,
+    /// Destinations of `staged`, for quick lookups
+    destinations: HashSet<PathBuf>,
+    /// Folders that did not exist before, in creation order
+    created_dirs: Vec<PathBuf>,
+}
+
+/// A destination replaced during commit, with the previous file if there was one
+struct Committed {
+    destination: PathBuf,
+    backup: Option<TempPath>,
+}
+
+impl Transaction {
+    /// Like `fs::create_dir_all`, remembering which folders are new
+    pub(crate) fn create_dir_all(&mut self, path: &Path) -> Result<()> {
+        // A relative path ends with an empty ancestor, which stands for the current directory
+        let missing: Vec<&Path> = path
+            .ancestors()
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+            .collect();
+        for dir in missing.into_iter().rev() {
+            fs::create_dir(dir).with_context(|| format!("Could not create folder '{}'", dir.display()))?;
+            self.created_dirs.push(dir.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Whether a file is already staged for `destination`
+    pub(crate) fn is_staged(&self, destination: &Path) -> bool {
+        self.destinations.contains(destination)
+    }
+
+    /// Stages the contents of `asset` for `destination`, returning their hash
+    pub(crate) fn stage(&mut self, destination: &Path, asset: &mut impl Read) -> Result<String> {
+        let (file, hash) = stage_file(destination, asset)?;
+        self.push(file, destination);
+        Ok(hash)
+    }
+
+    /// Stages `asset` unless `destination` already has the same content.
+    /// Returns whether it was staged, and the hash of `asset`.
+    pub(crate) fn stage_if_different(&mut self, destination: &Path, asset: &mut impl Read) -> Result<(bool, String)> {
+        let (file, hash) = stage_file(destination, asset)?;
+        if destination.exists() && hash == hash_file(destination)? {
+            return Ok((false, hash));
+        }
+        self.push(file, destination);
+        Ok((true, hash))
+    }
+
+    fn push(&mut self, file: TempPath, destination: &Path) {
+        self.destinations.insert(destination.to_path_buf());
+        self.staged.push((file, destination.to_path_buf()));
+    }
+
+    /// Moves every staged file into place; on failure, restores what was there before
+    pub(crate) fn commit(mut self) -> Result<()> {
+        let mut staged = std::mem::take(&mut self.staged).into_iter();
+        let mut committed: Vec<Committed> = Vec::with_capacity(staged.len());
+
+        while let Some((file, destination)) = staged.next() {
+            if let Err(e) = commit_file(file, &destination, &mut committed) {
+                // Delete the files not committed yet, so the folders created for them can go too
+                drop(staged);
+                restore(committed);
+                self.rollback();
+                return Err(e);
+            }
+        }
+        // Dropping the backups deletes them
+        Ok(())
+    }
+
+    /// Discards the staged files and removes the folders created for them
+    pub(crate) fn rollback(mut self) {
+        self.staged.clear();
+        for dir in self.created_dirs.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+fn stage_file(destination: &Path, asset: &mut impl Read) -> Result<(TempPath, String)> {
+    let parent = destination.parent().unwrap_or(Path::new("."));
+    let mut file = tempfile::Builder::new()
+        .prefix(".unitypackage-")
+        .tempfile_in(parent)
+        .with_context(|| format!("Could not create a temporary file in '{}'", parent.display()))?;
+    let hash = copy_hashed(asset, &mut file)?;
+    Ok((file.into_temp_path(), hash))
+}
+
+fn commit_file(file: TempPath, destination: &Path, committed: &mut Vec<Committed>) -> Result<()> {
+    let backup = if destination.exists() {
+        let parent = destination.parent().unwrap_or(Path::new("."));
+        let backup = tempfile::Builder::new()
+            .prefix(".unitypackage-backup-")
+            .tempfile_in(parent)
+            .with_context(|| format!("Could not back up '{}'", destination.display()))?
+            .into_temp_path();
+        fs::rename(destination, &backup).with_context(|| format!("Could not back up '{}'", destination.display()))?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    committed.push(Committed {
+        destination: destination.to_path_buf(),
+        backup,
+    });
+    file.persist(destination)
+        .with_context(|| format!("Could not move the new '{}' into place", destination.display()))?;
+    Ok(())
+}
+
+/// Undoes the committed files, newest first
+fn restore(committed: Vec<Committed>) {
+    for Committed { destination, backup } in committed.into_iter().rev() {
+        match backup {
+            Some(backup) => {
+                let _ = backup.persist(&destination);
+            }
+            None => {
+                let _ = fs::remove_file(&destination);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// Every file and folder under `root` with the contents of the files
+    fn snapshot(root: &Path) -> BTreeMap<PathBuf, Option<Vec<u8>>> {
+        fn walk(root: &Path, dir: &Path, tree: &mut BTreeMap<PathBuf, Option<Vec<u8>>>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                let relative = path.strip_prefix(root).unwrap().to_path_buf();
+                if path.is_dir() {
+                    tree.insert(relative, None);
+                    walk(root, &path, tree);
+                } else {
+                    tree.insert(relative, Some(fs::read(&path).unwrap()));
+                }
+            }
+        }
+        let mut tree = BTreeMap::new();
+        walk(root, root, &mut tree);
+        tree
+    }
+
+    #[test]
+    fn failed_commit_leaves_the_tree_as_before() {
+        let root = tempfile::tempdir().unwrap();
+        let out = root.path();
+        fs::write(out.join("existing.txt"), "old").unwrap();
+        // A non-empty folder where a file is expected cannot be backed up, so its commit fails
+        fs::create_dir_all(out.join("blocked.txt/inside")).unwrap();
+        let before = snapshot(out);
+
+        let scripts = out.join("Assets/Vendor/Scripts");
+        let mut transaction = Transaction::default();
+        transaction.create_dir_all(&scripts).unwrap();
+        transaction.stage(&out.join("existing.txt"), &mut "new".as_bytes()).unwrap();
+        transaction.stage(&scripts.join("A.cs"), &mut "a".as_bytes()).unwrap();
+        transaction.stage(&out.join("blocked.txt"), &mut "b".as_bytes()).unwrap();
+        transaction.stage(&scripts.join("C.cs"), &mut "c".as_bytes()).unwrap();
+
+        assert!(transaction.commit().is_err());
+        assert_eq!(snapshot(out), before);
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::extract::{FileKind, OutputResolver};
+use crate::hash::hash_file;
+use crate::manifest::{InstalledFile, Manifest};
+use crate::upgrade::remove_empty_folders;
+
+/// Options for [`uninstall`]
+#[derive(Debug, Clone, Default)]
+pub struct UninstallOptions {
+    /// Also delete files whose contents changed since they were extracted
+    pub force: bool,
+    /// Decide every action without touching the disk
+    pub dry_run: bool,
+}
+
+/// What happened to a single file or folder listed in the receipt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UninstallAction {
+    /// Deleted (for folders: they were left empty and deleted)
+    Removed,
+    /// Modified since extraction and deleted anyway ([`UninstallOptions::force`])
+    RemovedModified,
+    /// Modified since extraction, so it was kept
+    KeptModified,
+    /// A folder that still holds other files, so it was kept
+    KeptNotEmpty,
+    /// Already gone
+    Missing,
+    /// The pathname resolves outside the output directory (path traversal)
+    SkippedOutsideDestination,
+}
+
+impl UninstallAction {
+    /// Stable snake_case name, used in machine-readable output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UninstallAction::Removed => "removed",
+            UninstallAction::RemovedModified => "removed_modified",
+            UninstallAction::KeptModified => "kept_modified",
+            UninstallAction::KeptNotEmpty => "kept_not_empty",
+            UninstallAction::Missing => "missing",
+            UninstallAction::SkippedOutsideDestination => "skipped_outside_destination",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UninstallRecord {
+    pub guid: String,
+    pub kind: FileKind,
+    pub pathname: String,
+    pub destination: PathBuf,
+    pub action: UninstallAction,
+}
+
+/// Everything that was done (or would be done, for a dry run) while uninstalling
+#[derive(Debug, Clone, Default)]
+pub struct UninstallReport {
+    /// Files first, then folders from the deepest up
+    pub records: Vec<UninstallRecord>,
+    /// What is left of the receipt: the files and folders that were kept
+    pub remaining: Manifest,
+}
+
+impl UninstallReport {
+    /// Number of records that ended with `action`
+    pub fn count(&self, action: UninstallAction) -> usize {
+        self.records.iter().filter(|r| r.action == action).count()
+    }
+}
+
+/// Removes the files an extraction wrote into `output_path`, as listed in its receipt
+///
+/// Files whose SHA-256 no longer matches the receipt are kept unless
+/// [`UninstallOptions::force`] is set. Folders are only removed once empty, along with
+/// any parent folders the removal left empty.
+pub fn uninstall(output_path: &Path, receipt: &Manifest, options: &UninstallOptions) -> Result<UninstallReport> {
+    let resolver = OutputResolver::new(output_path);
+    let mut report = UninstallReport::default();
+    report.remaining.package = receipt.package.clone();
+    let mut emptied: Vec<PathBuf> = Vec::new();
+
+    let (folders, files): (Vec<&InstalledFile>, Vec<&InstalledFile>) =
+        receipt.files.iter().partition(|file| file.kind == FileKind::Folder);
+
+    for file in files {
+        let resolved = resolver.resolve(&file.pathname);
+        let action = if !resolved.inside {
+            UninstallAction::SkippedOutsideDestination
+        } else if !resolved.destination.is_file() {
+            UninstallAction::Missing
+        } else {
+            let modified = Some(hash_file(&resolved.destination)?) != file.hash;
+            if modified && !options.force {
+                UninstallAction::KeptModified
+            } else {
+                if !options.dry_run {
+                    fs::remove_file(&resolved.destination)
+                        .with_context(|| format!("Could not remove '{}'", resolved.destination.display()))?;
+                }
+                emptied.push(resolved.destination.clone());
+                if modified { UninstallAction::RemovedModified } else { UninstallAction::Removed }
+            }
+        };
+
+        if action == UninstallAction::KeptModified {
+            report.remaining.files.push(file.clone());
+        }
+        report.records.push(UninstallRecord {
+            guid: file.guid.clone(),
+            kind: file.kind,
+            pathname: resolved.pathname,
+            destination: resolved.destination,
+            action,
+        });
+    }
+
+    // Folders that exist before anything is removed, deepest first
+    let mut folders: Vec<(&InstalledFile, PathBuf, Option<bool>)> = folders
+        .into_iter()
+        .map(|folder| {
+            let resolved = resolver.resolve(&folder.pathname);
+            let existed = resolved.inside.then(|| resolved.destination.is_dir());
+            (folder, resolved.destination, existed)
+        })
+        .collect();
+    folders.sort_by_key(|(_, destination, _)| std::cmp::Reverse(destination.components().count()));
+
+    // For a dry run, a folder is expected to go if everything left in it is being removed
+    let mut removed: HashSet<PathBuf> = emptied.iter().cloned().collect();
+
+    if !options.dry_run {
+        emptied.extend(folders.iter().filter(|(_, _, existed)| *existed == Some(true)).map(|(_, d, _)| d.clone()));
+        remove_empty_folders(emptied.iter(), output_path, &HashSet::new());
+    }
+
+    for (folder, destination, existed) in folders {
+        let action = if existed.is_none() {
+            UninstallAction::SkippedOutsideDestination
+        } else if existed == Some(false) {
+            UninstallAction::Missing
+        } else if !destination.exists() {
+            UninstallAction::Removed
+        } else if options.dry_run && only_contains(&destination, &removed)? {
+            removed.insert(destination.clone());
+            UninstallAction::Removed
+        } else {
+            UninstallAction::KeptNotEmpty
+        };
+
+        if action == UninstallAction::KeptNotEmpty {
+            report.remaining.files.push(folder.clone());
+        }
+        report.records.push(UninstallRecord {
+            guid: folder.guid.clone(),
+            kind: folder.kind,
+            pathname: folder.pathname.clone(),
+            destination,
+            action,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Whether every child of `folder` is about to be removed
+fn only_contains(folder: &Path, removed: &HashSet<PathBuf>) -> Result<bool> {
+    for child in fs::read_dir(folder).with_context(|| format!("Could not read folder '{}'", folder.display()))? {
+        let child = child?.path();
+        if !removed.contains(&child) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
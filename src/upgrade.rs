@@ -0,0 +1,529 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diff::EntryDigest;
+use crate::extract::{FileKind, OutputResolver, Resolved, write_new};
+use crate::hash::hash_file;
+use crate::manifest::{InstalledFile, Manifest};
+use crate::package::{PackageArchive, split_entry_path};
+
+/// Options for [`UnityPackage::upgrade`](crate::UnityPackage::upgrade)
+#[derive(Debug, Clone)]
+pub struct UpgradeOptions {
+    /// Keep the `.meta` files in sync too; when disabled, previously written metas are left alone
+    pub extract_meta: bool,
+    /// Decide every action without touching the disk
+    pub dry_run: bool,
+}
+
+impl Default for UpgradeOptions {
+    fn default() -> Self {
+        Self {
+            extract_meta: true,
+            dry_run: false,
+        }
+    }
+}
+
+/// What happened to a single file when applying the new version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeAction {
+    /// New in this version, written at its pathname
+    Added,
+    /// Modified upstream and untouched locally: replaced with the new version
+    Updated,
+    /// Same content upstream and on disk
+    Unchanged,
+    /// Modified locally but not upstream: the local changes are kept
+    KeptLocal,
+    /// Removed upstream and untouched locally: deleted
+    Removed,
+    /// Deleted locally and not modified upstream: left deleted
+    DeletedLocally,
+    /// Needs a manual decision; the file on disk is left as it was
+    Conflict(UpgradeConflict),
+    /// The pathname resolves outside the destination directory (path traversal)
+    SkippedOutsideDestination,
+}
+
+impl UpgradeAction {
+    /// Stable snake_case name, used in machine-readable output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UpgradeAction::Added => "added",
+            UpgradeAction::Updated => "updated",
+            UpgradeAction::Unchanged => "unchanged",
+            UpgradeAction::KeptLocal => "kept_local",
+            UpgradeAction::Removed => "removed",
+            UpgradeAction::DeletedLocally => "deleted_locally",
+            UpgradeAction::Conflict(_) => "conflict",
+            UpgradeAction::SkippedOutsideDestination => "skipped_outside_destination",
+        }
+    }
+}
+
+/// Why a file could not be upgraded automatically
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeConflict {
+    /// Modified both locally and upstream
+    BothModified,
+    /// Modified locally and removed upstream
+    RemovedUpstream,
+    /// Deleted locally and modified upstream
+    DeletedLocally,
+    /// A file the previous extraction did not write is in the way
+    Untracked,
+}
+
+impl UpgradeConflict {
+    /// Stable snake_case name, used in machine-readable output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UpgradeConflict::BothModified => "both_modified",
+            UpgradeConflict::RemovedUpstream => "removed_upstream",
+            UpgradeConflict::DeletedLocally => "deleted_locally",
+            UpgradeConflict::Untracked => "untracked",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            UpgradeConflict::BothModified => "modified both locally and upstream",
+            UpgradeConflict::RemovedUpstream => "modified locally but removed upstream",
+            UpgradeConflict::DeletedLocally => "deleted locally but modified upstream",
+            UpgradeConflict::Untracked => "an existing file not from the package is in the way",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UpgradeRecord {
+    pub guid: String,
+    pub kind: FileKind,
+    /// Output pathname after the upgrade (the current one for removals and conflicts)
+    pub pathname: String,
+    /// Previous output pathname, when the GUID moved and the file moved with it
+    pub moved_from: Option<String>,
+    pub destination: PathBuf,
+    pub action: UpgradeAction,
+}
+
+/// Everything that was done (or would be done, for a dry run) while upgrading
+#[derive(Debug, Clone, Default)]
+pub struct UpgradeReport {
+    /// One record per file or folder, sorted by pathname, followed by the ones removed upstream
+    pub records: Vec<UpgradeRecord>,
+    /// Manifest describing the output directory after the upgrade
+    pub manifest: Manifest,
+}
+
+impl UpgradeReport {
+    /// Number of records that ended with `action`
+    pub fn count(&self, action: UpgradeAction) -> usize {
+        self.records.iter().filter(|r| r.action == action).count()
+    }
+
+    pub fn conflicts(&self) -> impl Iterator<Item = &UpgradeRecord> {
+        self.records.iter().filter(|r| matches!(r.action, UpgradeAction::Conflict(_)))
+    }
+}
+
+/// Disk changes decided while planning, applied once every file has been looked at
+#[derive(Default)]
+struct Changes {
+    removals: Vec<PathBuf>,
+    moves: Vec<(PathBuf, PathBuf)>,
+    folders: Vec<PathBuf>,
+    /// Destination of each package file that needs its new contents
+    writes: HashMap<(String, FileKind), PathBuf>,
+    /// Folders that may have been left empty
+    emptied: Vec<PathBuf>,
+}
+
+struct Upgrader {
+    resolver: OutputResolver,
+    report: UpgradeReport,
+    changes: Changes,
+}
+
+impl Upgrader {
+    /// Compares a package file with its previous version and what is on disk
+    fn plan(&mut self, file: InstalledFile, previous: Option<(InstalledFile, Resolved)>) -> Result<()> {
+        let resolved = self.resolver.resolve(&file.pathname);
+        if !resolved.inside {
+            self.record(&file, resolved, None, UpgradeAction::SkippedOutsideDestination);
+            if let Some((previous, _)) = previous {
+                self.report.manifest.files.push(previous);
+            }
+            return Ok(());
+        }
+        let installed = InstalledFile {
+            pathname: resolved.pathname.clone(),
+            ..file.clone()
+        };
+
+        if file.kind == FileKind::Folder {
+            self.changes.folders.push(resolved.destination.clone());
+            let (action, moved_from) = match previous {
+                None => (UpgradeAction::Added, None),
+                Some((previous, old)) if old.pathname != resolved.pathname => {
+                    self.changes.emptied.push(old.destination);
+                    (UpgradeAction::Unchanged, Some(previous.pathname))
+                }
+                Some(_) => (UpgradeAction::Unchanged, None),
+            };
+            self.record(&file, resolved, moved_from, action);
+            self.report.manifest.files.push(installed);
+            return Ok(());
+        }
+
+        let Some((previous, old)) = previous else {
+            let action = if !resolved.destination.exists() {
+                self.changes.writes.insert((file.guid.clone(), file.kind), resolved.destination.clone());
+                UpgradeAction::Added
+            } else if local_hash(&resolved.destination)? == file.hash {
+                UpgradeAction::Unchanged
+            } else {
+                UpgradeAction::Conflict(UpgradeConflict::Untracked)
+            };
+            if action != UpgradeAction::Conflict(UpgradeConflict::Untracked) {
+                self.report.manifest.files.push(installed);
+            }
+            self.record(&file, resolved, None, action);
+            return Ok(());
+        };
+
+        let moved = old.pathname != resolved.pathname;
+        let local = local_hash(&old.destination)?;
+        let modified_upstream = file.hash != previous.hash;
+        let modified_locally = local != previous.hash && local != file.hash;
+
+        // Conflicts leave the file where it is and keep its previous manifest record
+        let conflict = if local.is_none() {
+            modified_upstream.then_some(UpgradeConflict::DeletedLocally)
+        } else if modified_locally && modified_upstream {
+            Some(UpgradeConflict::BothModified)
+        } else if moved && resolved.destination.exists() {
+            Some(UpgradeConflict::Untracked)
+        } else {
+            None
+        };
+        if let Some(conflict) = conflict {
+            self.record(&previous, old, None, UpgradeAction::Conflict(conflict));
+            self.report.manifest.files.push(previous);
+            return Ok(());
+        }
+
+        let action = if local.is_none() {
+            UpgradeAction::DeletedLocally
+        } else if modified_locally {
+            UpgradeAction::KeptLocal
+        } else if local != file.hash {
+            UpgradeAction::Updated
+        } else {
+            UpgradeAction::Unchanged
+        };
+
+        if action == UpgradeAction::Updated {
+            self.changes.writes.insert((file.guid.clone(), file.kind), resolved.destination.clone());
+            if moved {
+                self.changes.removals.push(old.destination.clone());
+            }
+        } else if moved && action != UpgradeAction::DeletedLocally {
+            self.changes.moves.push((old.destination.clone(), resolved.destination.clone()));
+        }
+        if moved {
+            self.changes.emptied.push(old.destination);
+        }
+
+        let moved_from = (moved && action != UpgradeAction::DeletedLocally).then_some(previous.pathname);
+        self.record(&file, resolved, moved_from, action);
+        self.report.manifest.files.push(installed);
+        Ok(())
+    }
+
+    /// Handles a file of the previous version that is no longer in the package
+    fn plan_removal(&mut self, previous: InstalledFile, old: Resolved) -> Result<()> {
+        let action = if previous.kind == FileKind::Folder {
+            self.changes.emptied.push(old.destination.clone());
+            UpgradeAction::Removed
+        } else {
+            match local_hash(&old.destination)? {
+                None => return Ok(()),
+                Some(local) if Some(&local) == previous.hash.as_ref() => {
+                    self.changes.removals.push(old.destination.clone());
+                    self.changes.emptied.push(old.destination.clone());
+                    UpgradeAction::Removed
+                }
+                Some(_) => UpgradeAction::Conflict(UpgradeConflict::RemovedUpstream),
+            }
+        };
+
+        self.record(&previous, old, None, action);
+        if matches!(action, UpgradeAction::Conflict(_)) {
+            self.report.manifest.files.push(previous);
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, file: &InstalledFile, resolved: Resolved, moved_from: Option<String>, action: UpgradeAction) {
+        self.report.records.push(UpgradeRecord {
+            guid: file.guid.clone(),
+            kind: file.kind,
+            pathname: resolved.pathname,
+            moved_from,
+            destination: resolved.destination,
+            action,
+        });
+    }
+}
+
+/// Content hash of a file on disk, or `None` if it does not exist
+fn local_hash(path: &Path) -> Result<Option<String>> {
+    if path.is_file() { hash_file(path).map(Some) } else { Ok(None) }
+}
+
+/// Files the package would write, sorted by pathname
+fn upstream_files(digests: BTreeMap<String, EntryDigest>, extract_meta: bool) -> Vec<InstalledFile> {
+    let mut digests: Vec<(String, EntryDigest)> = digests.into_iter().collect();
+    digests.sort_by(|a, b| a.1.pathname.cmp(&b.1.pathname));
+
+    let mut files = Vec::new();
+    for (guid, digest) in digests {
+        if digest.is_folder {
+            files.push(InstalledFile {
+                guid: guid.clone(),
+                kind: FileKind::Folder,
+                pathname: digest.pathname.clone(),
+                hash: None,
+            });
+        } else if digest.asset_hash.is_some() {
+            files.push(InstalledFile {
+                guid: guid.clone(),
+                kind: FileKind::Asset,
+                pathname: digest.pathname.clone(),
+                hash: digest.asset_hash,
+            });
+        }
+        if extract_meta && digest.meta_hash.is_some() {
+            files.push(InstalledFile {
+                guid,
+                kind: FileKind::Meta,
+                pathname: FileKind::Meta.output_pathname(&digest.pathname),
+                hash: digest.meta_hash,
+            });
+        }
+    }
+    files
+}
+
+/// Applies a package over the output of a previous extraction described by `previous`
+///
+/// `digests` come from a first pass over the package; `archive` is streamed a second
+/// time to write the added and updated files.
+pub(crate) fn upgrade_archive(
+    digests: BTreeMap<String, EntryDigest>,
+    archive: PackageArchive,
+    output_path: &Path,
+    previous: &Manifest,
+    options: &UpgradeOptions,
+) -> Result<UpgradeReport> {
+    let mut upgrader = Upgrader {
+        resolver: OutputResolver::new(output_path),
+        report: UpgradeReport::default(),
+        changes: Changes::default(),
+    };
+
+    // Manifest records pointing outside the output directory are never touched
+    let mut installed: HashMap<(String, FileKind), (InstalledFile, Resolved)> = HashMap::new();
+    let mut order = Vec::new();
+    for file in &previous.files {
+        if !options.extract_meta && file.kind == FileKind::Meta {
+            upgrader.report.manifest.files.push(file.clone());
+            continue;
+        }
+        let resolved = upgrader.resolver.resolve(&file.pathname);
+        if resolved.inside {
+            let key = (file.guid.clone(), file.kind);
+            order.push(key.clone());
+            installed.insert(key, (file.clone(), resolved));
+        }
+    }
+
+    for file in upstream_files(digests, options.extract_meta) {
+        let previous = installed.remove(&(file.guid.clone(), file.kind));
+        upgrader.plan(file, previous)?;
+    }
+    for key in order {
+        if let Some((file, resolved)) = installed.remove(&key) {
+            upgrader.plan_removal(file, resolved)?;
+        }
+    }
+
+    if !options.dry_run {
+        apply(&upgrader.changes, archive, output_path)?;
+    }
+    Ok(upgrader.report)
+}
+
+fn apply(changes: &Changes, mut archive: PackageArchive, output_path: &Path) -> Result<()> {
+    for path in &changes.removals {
+        fs::remove_file(path).with_context(|| format!("Could not remove '{}'", path.display()))?;
+    }
+    for (from, to) in &changes.moves {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(from, to).with_context(|| format!("Could not move '{}' to '{}'", from.display(), to.display()))?;
+    }
+    for folder in &changes.folders {
+        fs::create_dir_all(folder).with_context(|| format!("Could not create folder '{}'", folder.display()))?;
+    }
+
+    if !changes.writes.is_empty() {
+        for entry in archive.entries().context("Error reading package contents")? {
+            let mut entry = entry.context("Error reading package contents")?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let Some((guid, name)) = split_entry_path(&entry.path()?) else {
+                continue;
+            };
+            let kind = match name.as_str() {
+                "asset" => FileKind::Asset,
+                "asset.meta" => FileKind::Meta,
+                _ => continue,
+            };
+
+            if let Some(destination) = changes.writes.get(&(guid, kind)) {
+                if let Some(parent) = destination.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                write_new(destination, &mut entry, false)?;
+            }
+        }
+    }
+
+    let keep: HashSet<&PathBuf> = changes.folders.iter().collect();
+    remove_empty_folders(changes.emptied.iter(), output_path, &keep);
+    Ok(())
+}
+
+/// Removes each path (if it is an empty folder) and its parents that became empty,
+/// stopping at `root` and at the folders in `keep`
+pub(crate) fn remove_empty_folders<'a>(paths: impl Iterator<Item = &'a PathBuf>, root: &Path, keep: &HashSet<&PathBuf>) {
+    // Deepest first, so a folder is only checked once its children are gone
+    let mut paths: Vec<&PathBuf> = paths.collect();
+    paths.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+
+    for path in paths {
+        for dir in path.ancestors() {
+            if dir == root || !dir.starts_with(root) || keep.contains(&dir.to_path_buf()) {
+                break;
+            }
+            // Fails on files and non-empty folders, which is exactly when to stop
+            if dir.is_dir() && fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::copy_hashed;
+    use std::io;
+
+    const PATHNAME: &str = "Assets/A.cs";
+
+    fn hash(contents: &str) -> Option<String> {
+        Some(copy_hashed(&mut contents.as_bytes(), &mut io::sink()).unwrap())
+    }
+
+    fn file(contents: &str) -> InstalledFile {
+        InstalledFile {
+            guid: "a1".to_string(),
+            kind: FileKind::Asset,
+            pathname: PATHNAME.to_string(),
+            hash: hash(contents),
+        }
+    }
+
+    /// Plans a single file, with `local` on disk (or deleted), returning the upgrader
+    fn plan(local: Option<&str>, previous: Option<&str>, upstream: Option<&str>) -> Upgrader {
+        let output = tempfile::tempdir().unwrap();
+        if let Some(local) = local {
+            fs::create_dir_all(output.path().join("Assets")).unwrap();
+            fs::write(output.path().join(PATHNAME), local).unwrap();
+        }
+
+        let mut upgrader = Upgrader {
+            resolver: OutputResolver::new(output.path()),
+            report: UpgradeReport::default(),
+            changes: Changes::default(),
+        };
+        let previous = previous.map(|contents| (file(contents), upgrader.resolver.resolve(PATHNAME)));
+        match (upstream, previous) {
+            (Some(upstream), previous) => upgrader.plan(file(upstream), previous).unwrap(),
+            (None, Some((previous, old))) => upgrader.plan_removal(previous, old).unwrap(),
+            (None, None) => unreachable!(),
+        }
+        upgrader
+    }
+
+    fn action(local: Option<&str>, previous: Option<&str>, upstream: Option<&str>) -> Option<UpgradeAction> {
+        plan(local, previous, upstream).report.records.first().map(|record| record.action)
+    }
+
+    #[test]
+    fn conflict_matrix() {
+        use UpgradeAction::*;
+        let (v1, v2, mine) = (Some("v1"), Some("v2"), Some("mine"));
+
+        // (on disk, previous version, new version)
+        assert_eq!(action(v1, v1, v1), Some(Unchanged));
+        assert_eq!(action(v1, v1, v2), Some(Updated));
+        assert_eq!(action(v2, v1, v2), Some(Unchanged));
+        assert_eq!(action(mine, v1, v1), Some(KeptLocal));
+        assert_eq!(action(mine, v1, v2), Some(Conflict(UpgradeConflict::BothModified)));
+        assert_eq!(action(None, v1, v1), Some(DeletedLocally));
+        assert_eq!(action(None, v1, v2), Some(Conflict(UpgradeConflict::DeletedLocally)));
+
+        // New in this version
+        assert_eq!(action(None, None, v2), Some(Added));
+        assert_eq!(action(v2, None, v2), Some(Unchanged));
+        assert_eq!(action(mine, None, v2), Some(Conflict(UpgradeConflict::Untracked)));
+
+        // Removed upstream
+        assert_eq!(action(v1, v1, None), Some(Removed));
+        assert_eq!(action(mine, v1, None), Some(Conflict(UpgradeConflict::RemovedUpstream)));
+        assert_eq!(action(None, v1, None), None);
+    }
+
+    #[test]
+    fn only_updates_are_written() {
+        let updated = plan(Some("v1"), Some("v1"), Some("v2"));
+        assert!(updated.changes.writes.contains_key(&("a1".to_string(), FileKind::Asset)));
+
+        for upgrader in [plan(Some("mine"), Some("v1"), Some("v1")), plan(Some("mine"), Some("v1"), Some("v2"))] {
+            assert!(upgrader.changes.writes.is_empty());
+            assert!(upgrader.changes.removals.is_empty());
+        }
+    }
+
+    #[test]
+    fn conflicts_keep_the_previous_manifest_record() {
+        let upgrader = plan(Some("mine"), Some("v1"), Some("v2"));
+        assert_eq!(upgrader.report.manifest.files, [file("v1")]);
+
+        let upgrader = plan(Some("mine"), None, Some("v2"));
+        assert!(upgrader.report.manifest.files.is_empty());
+
+        let upgrader = plan(Some("v1"), Some("v1"), Some("v2"));
+        assert_eq!(upgrader.report.manifest.files, [file("v2")]);
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read};
+use std::path::Component;
+
+use crate::package::{PackageArchive, is_folder_meta, is_valid_guid, meta_guid, read_pathname, split_entry_path};
+
+/// Kind of problem found by [`UnityPackage::verify`](crate::UnityPackage::verify)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IssueCategory {
+    /// The gzip stream or tar archive is damaged (bad CRC, truncated data, ...)
+    Corrupt,
+    /// An archive entry that does not follow the `<guid>/<file>` layout
+    UnexpectedEntry,
+    /// A GUID directory name that is not 32 lowercase hexadecimal digits
+    MalformedGuid,
+    /// A GUID directory without a `pathname`
+    MissingPathname,
+    /// A GUID directory with a pathname but neither an `asset` nor a folder meta
+    MissingAsset,
+    /// A GUID directory without an `asset.meta`
+    MissingMeta,
+    /// The `guid:` in `asset.meta` differs from the directory name
+    GuidMismatch,
+    /// Several GUIDs share the same pathname
+    DuplicatePathname,
+    /// A pathname that is empty, absolute or escapes the project with `..`
+    UnsafePathname,
+}
+
+impl IssueCategory {
+    /// Stable snake_case name, used in machine-readable output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IssueCategory::Corrupt => "corrupt",
+            IssueCategory::UnexpectedEntry => "unexpected_entry",
+            IssueCategory::MalformedGuid => "malformed_guid",
+            IssueCategory::MissingPathname => "missing_pathname",
+            IssueCategory::MissingAsset => "missing_asset",
+            IssueCategory::MissingMeta => "missing_meta",
+            IssueCategory::GuidMismatch => "guid_mismatch",
+            IssueCategory::DuplicatePathname => "duplicate_pathname",
+            IssueCategory::UnsafePathname => "unsafe_pathname",
+        }
+    }
+
+    /// Human-readable heading for reports
+    pub fn description(self) -> &'static str {
+        match self {
+            IssueCategory::Corrupt => "Corrupt archive",
+            IssueCategory::UnexpectedEntry => "Unexpected archive entries",
+            IssueCategory::MalformedGuid => "Malformed GUID directory names",
+            IssueCategory::MissingPathname => "Missing pathname",
+            IssueCategory::MissingAsset => "Missing asset",
+            IssueCategory::MissingMeta => "Missing asset.meta",
+            IssueCategory::GuidMismatch => "GUID in asset.meta does not match its directory",
+            IssueCategory::DuplicatePathname => "Duplicate pathnames",
+            IssueCategory::UnsafePathname => "Unsafe pathnames",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifyIssue {
+    pub category: IssueCategory,
+    /// GUID directory (or archive path, for unexpected entries) the issue refers to
+    pub guid: Option<String>,
+    pub message: String,
+}
+
+/// Result of verifying a package
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub issues: Vec<VerifyIssue>,
+    /// Number of GUID directories found
+    pub entries: usize,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Issues grouped by category, in category order
+    pub fn by_category(&self) -> BTreeMap<IssueCategory, Vec<&VerifyIssue>> {
+        let mut groups: BTreeMap<IssueCategory, Vec<&VerifyIssue>> = BTreeMap::new();
+        for issue in &self.issues {
+            groups.entry(issue.category).or_default().push(issue);
+        }
+        groups
+    }
+
+    fn push(&mut self, category: IssueCategory, guid: Option<&str>, message: String) {
+        self.issues.push(VerifyIssue {
+            category,
+            guid: guid.map(str::to_string),
+            message,
+        });
+    }
+}
+
+#[derive(Default)]
+struct GuidState {
+    pathname: Option<String>,
+    has_asset: bool,
+    meta: Option<Vec<u8>>,
+}
+
+/// Reads the whole archive, including the gzip trailer, and checks the package layout
+pub(crate) fn verify_archive(mut archive: PackageArchive) -> Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+    let mut guids: BTreeMap<String, GuidState> = BTreeMap::new();
+
+    if let Err(e) = read_entries(&mut archive, &mut guids, &mut report) {
+        report.push(IssueCategory::Corrupt, None, format!("Error reading the archive: {}", e));
+    } else {
+        // Tar stops at its end-of-archive marker; drain the rest so gzip checks its CRC and length
+        let mut decoder = archive.into_inner();
+        if let Err(e) = io::copy(&mut decoder, &mut io::sink()) {
+            report.push(IssueCategory::Corrupt, None, format!("Error reading the gzip stream: {}", e));
+        }
+    }
+
+    report.entries = guids.len();
+    let mut by_pathname: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for (guid, state) in &guids {
+        if !is_valid_guid(guid) {
+            report.push(
+                IssueCategory::MalformedGuid,
+                Some(guid),
+                format!("'{}' is not a 32-digit lowercase hexadecimal GUID", guid),
+            );
+        }
+
+        let is_folder = state.meta.as_deref().is_some_and(is_folder_meta);
+
+        match &state.pathname {
+            None => report.push(IssueCategory::MissingPathname, Some(guid), format!("'{}' has no pathname", guid)),
+            Some(pathname) => {
+                if !is_safe_pathname(pathname) {
+                    report.push(
+                        IssueCategory::UnsafePathname,
+                        Some(guid),
+                        format!("'{}' has the unsafe pathname '{}'", guid, pathname),
+                    );
+                }
+                if !state.has_asset && !is_folder {
+                    report.push(
+                        IssueCategory::MissingAsset,
+                        Some(guid),
+                        format!("'{}' ({}) has neither an asset nor a folder meta", guid, pathname),
+                    );
+                }
+                by_pathname.entry(pathname).or_default().push(guid);
+            }
+        }
+
+        match &state.meta {
+            None => report.push(IssueCategory::MissingMeta, Some(guid), format!("'{}' has no asset.meta", guid)),
+            Some(meta) => match meta_guid(meta) {
+                Some(declared) if declared == *guid => {}
+                Some(declared) => report.push(
+                    IssueCategory::GuidMismatch,
+                    Some(guid),
+                    format!("'{}' has an asset.meta declaring GUID '{}'", guid, declared),
+                ),
+                None => report.push(
+                    IssueCategory::GuidMismatch,
+                    Some(guid),
+                    format!("'{}' has an asset.meta without a valid GUID", guid),
+                ),
+            },
+        }
+    }
+
+    let mut duplicates: Vec<(&str, Vec<&str>)> = by_pathname.into_iter().filter(|(_, g)| g.len() > 1).collect();
+    duplicates.sort();
+    for (pathname, owners) in duplicates {
+        report.push(
+            IssueCategory::DuplicatePathname,
+            Some(owners[0]),
+            format!("'{}' is used by {} GUIDs: {}", pathname, owners.len(), owners.join(", ")),
+        );
+    }
+
+    Ok(report)
+}
+
+fn read_entries(
+    archive: &mut PackageArchive,
+    guids: &mut BTreeMap<String, GuidState>,
+    report: &mut VerifyReport,
+) -> io::Result<()> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let entry_type = entry.header().entry_type();
+
+        if entry_type.is_dir() {
+            // `<guid>/` directory entries are optional but valid
+            if path.components().filter(|c| !matches!(c, Component::CurDir)).count() == 1 {
+                guids.entry(path.to_string_lossy().trim_end_matches('/').to_string()).or_default();
+                continue;
+            }
+        }
+
+        let Some((guid, name)) = split_entry_path(&path).filter(|_| entry_type.is_file()) else {
+            let path = path.to_string_lossy();
+            report.push(
+                IssueCategory::UnexpectedEntry,
+                Some(&path),
+                format!("'{}' does not follow the <guid>/<file> layout", path),
+            );
+            continue;
+        };
+
+        let state = guids.entry(guid.clone()).or_default();
+        match name.as_str() {
+            "pathname" => {
+                state.pathname = Some(read_pathname(&mut entry).map_err(io::Error::other)?);
+            }
+            "asset" => {
+                state.has_asset = true;
+                // Read through the data so truncation is detected here
+                io::copy(&mut entry, &mut io::sink())?;
+            }
+            "asset.meta" => {
+                let mut meta = Vec::new();
+                entry.read_to_end(&mut meta)?;
+                state.meta = Some(meta);
+            }
+            "preview.png" => {
+                io::copy(&mut entry, &mut io::sink())?;
+            }
+            _ => report.push(
+                IssueCategory::UnexpectedEntry,
+                Some(&guid),
+                format!("'{}/{}' is not a known package file", guid, name),
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Whether a pathname stays inside the project: relative, non-empty and without `..`
+/// (checked with both separators, since packages come from any platform)
+fn is_safe_pathname(pathname: &str) -> bool {
+    !pathname.is_empty()
+        && !pathname.starts_with(['/', '\\'])
+        && pathname.get(1..2) != Some(":")
+        && pathname.split(['/', '\\']).all(|part| part != "..")
+}